@@ -1,41 +1,55 @@
 use crate::{
+    iconst,
+    interface::{self, load_fonts, load_style, InterfacePage, Theme},
+};
+use songdl::{
     command::{
-        convert_audio, download_audio, download_thumbnail, extract_metadata, extract_thumbnail,
-        set_command, DEFAULT_FFMPEG_COMMAND, DEFAULT_YT_DL_COMMAND,
+        check_cancelled, check_command, current_cancel_token, download_playlist,
+        kill_running_commands, latest_ytdl_version, open_in_editor, reveal_in_file_browser,
+        set_cancel_token, set_command, tempfile_with_suffix, update_ytdl, AudioFormat, Bitrate,
+        CancelToken, Cancelled, EncodeOptions, SampleFormat, YtdlOptions, DEFAULT_FFMPEG_COMMAND,
+        DEFAULT_YT_DL_COMMAND, YT_DL_FORMAT_SELECTOR,
+    },
+    musicbrainz::{self, MatchCandidate},
+    pipeline::{set_cover_from_image, SongPipeline, Status},
+    song::{
+        is_audio_path, is_playlist_link, remove_characters, CoverFit, FolderStructure,
+        LoudnessMode, Origin, Song, WaveformMode, DEFAULT_TITLE_JUNK_PATTERNS,
     },
-    iconst,
-    interface::{self, load_fonts, load_style, InterfacePage},
-    song::Song,
 };
 
 use anyhow::{bail, Context as ErrorContext, Result};
+use chrono::TimeZone;
+use crossbeam_channel::{Receiver, Sender};
 use eframe::{self};
-use egui::{ColorImage, Context, TextureHandle, TextureOptions};
+use egui::{ColorImage, Context, Pos2, TextureHandle, TextureOptions, Vec2};
 use egui_notify::{ToastOptions, ToastUpdate, Toasts};
 use figment::{
     providers::{Format, Serialized},
     Figment,
 };
 
-use image::{imageops, DynamicImage};
+use image::{imageops, DynamicImage, RgbaImage};
 use kira::{
+    dsp::Frame,
     manager::{backend::DefaultBackend, AudioManager, AudioManagerSettings},
-    sound::static_sound::StaticSoundHandle,
+    sound::static_sound::{StaticSoundData, StaticSoundHandle},
     tween::Tween,
 };
 use poll_promise::Promise;
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use serde_json::json;
+use parking_lot::Mutex;
 use std::{
+    collections::{HashMap, VecDeque},
     fs,
-    io::{Cursor, Write},
+    ops::RangeInclusive,
     path::PathBuf,
-    time::Duration,
+    sync::Arc,
+    thread,
+    time::{Duration, Instant},
 };
 
-use crate::song::Origin;
-use tempfile::NamedTempFile;
-
 #[derive(Default)]
 pub struct App {
     pub toasts: Toasts,
@@ -44,6 +58,44 @@ pub struct App {
     pub settings: Settings,
     pub downloader_state: DownloaderState,
     pub audio_manager: Option<AudioManager>,
+    pub report: Arc<Mutex<BatchReport>>,
+    pub history: Arc<Mutex<History>>,
+
+    pub ytdl_info: Option<YtdlInfo>,
+    pub loading_ytdl_info: Option<Promise<Result<YtdlInfo>>>,
+    pub updating_ytdl: Option<Promise<Result<String>>>,
+
+    pub command_checks: HashMap<&'static str, Result<String>>,
+    pub checking_commands: Option<Promise<Vec<(&'static str, Result<String>)>>>,
+
+    /// a close was requested while something was loading
+    pub confirming_quit: bool,
+    pub quit_confirmed: bool,
+    /// quit once nothing is loading anymore, unless it's cancelled first
+    pub quit_when_done: bool,
+
+    /// left over from the last run, until it's restored or discarded
+    pub saved_session: Option<Session>,
+    /// applied once the restored song is queried again
+    pub restoring_session: Option<Session>,
+    pub last_session_save: Option<(Instant, Session)>,
+
+    /// the token of the last op started with `spawn_cancellable`, flipped by `cancel`
+    pub cancel_token: CancelToken,
+}
+
+#[derive(Clone)]
+pub struct YtdlInfo {
+    pub installed_version: String,
+    pub latest_version: Option<String>,
+}
+
+impl YtdlInfo {
+    pub fn is_outdated(&self) -> bool {
+        self.latest_version
+            .as_ref()
+            .is_some_and(|latest| *latest != self.installed_version)
+    }
 }
 
 pub const SETTINGS_FILENAME: &str = "settings.toml";
@@ -53,14 +105,387 @@ pub struct DownloaderState {
     pub song: Song,
     pub song_handle: Option<StaticSoundHandle>,
     pub song_origin: Origin,
+    pub cover_texture: Option<CoverTexture>,
     pub save_path: PathBuf,
+    pub last_saved_path: Arc<Mutex<Option<PathBuf>>>,
     pub loading_song: Option<Promise<Result<Song>>>,
 
+    pub queue: Vec<QueueEntry>,
+    pub queue_index: usize,
+    pub loading_queue: Option<Promise<Result<()>>>,
+    pub queue_receiver: Option<Receiver<QueueUpdate>>,
+
+    pub progress: Option<f32>,
+    pub progress_receiver: Option<Receiver<Option<f32>>>,
+
     pub volume_offset: String,
+    pub target_peak: String,
+    pub trim_start: String,
+    pub trim_end: String,
+    pub editing_lyrics: bool,
+    pub match_candidates: Vec<MatchCandidate>,
+    pub looking_up_matches: Option<Promise<Result<Vec<MatchCandidate>>>>,
+    pub overwrite_matched_tags: bool,
+    pub preview_channel: PreviewChannel,
+    pub repeat: bool,
+    /// set by every stop so only a song that played to its end advances the queue
+    pub suppress_auto_advance: bool,
+    pub muted: bool,
+    pub scrub_ratio: Option<f32>,
+    pub last_scrub_seek: Option<Instant>,
+
+    pub batch_tags: BatchTags,
+    /// the last applied batch, queue entries that finish afterwards get it too
+    pub applied_batch_tags: Option<BatchTags>,
+}
+
+/// the shown song's cover as a texture, remade whenever the cover or how it's fit changes
+pub struct CoverTexture {
+    cover_bytes: Arc<[u8]>,
+    cover_fit: CoverFit,
+    /// `None` when the cover couldn't be decoded, so it isn't retried every frame
+    texture_handle: Option<TextureHandle>,
+}
+
+/// tags written onto every song in the queue by `App::apply_to_queue`, empty fields are skipped
+#[derive(Default, Clone)]
+pub struct BatchTags {
+    pub album: String,
+    pub album_artist: String,
+    pub genre: String,
+    pub year: String,
+    /// also replace the album and album artist of songs that set them separately
+    pub overwrite: bool,
+}
+
+impl BatchTags {
+    fn apply(&self, song: &mut Song) {
+        // fields an earlier batch set don't count as set separately
+        let fill = |field: &mut String, separate: &mut bool, from_batch: &mut bool, value: &str| {
+            if !value.is_empty() && (self.overwrite || !*separate || *from_batch) {
+                *field = value.to_string();
+                *separate = true;
+                *from_batch = true;
+            }
+        };
+        fill(
+            &mut song.album,
+            &mut song.separate_album,
+            &mut song.album_from_batch,
+            &self.album,
+        );
+        fill(
+            &mut song.album_artist,
+            &mut song.separate_album_artist,
+            &mut song.album_artist_from_batch,
+            &self.album_artist,
+        );
+        if !self.genre.is_empty() {
+            song.genre = self.genre.clone();
+        }
+        if !self.year.is_empty() {
+            song.year = self.year.clone();
+        }
+    }
+}
+
+/// what `next_song`/`prev_song` do past the ends of the queue
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum QueueEnd {
+    #[default]
+    Stop,
+    Wrap,
+}
+
+impl QueueEnd {
+    pub const ALL: [QueueEnd; 2] = [Self::Stop, Self::Wrap];
+}
+
+impl std::fmt::Display for QueueEnd {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stop => write!(f, "stop"),
+            Self::Wrap => write!(f, "wrap around"),
+        }
+    }
+}
+
+#[derive(Default, Clone, PartialEq)]
+pub enum QueueStatus {
+    #[default]
+    Queued,
+    Downloading,
+    Done,
+    Failed(String),
+}
+
+/// the song stays default until the entry is done, the selected entry's song is swapped out
+/// into `DownloaderState::song`
+#[derive(Default)]
+pub struct QueueEntry {
+    pub title: String,
+    pub status: QueueStatus,
+    pub song: Song,
+}
+
+/// sent by the playlist workers, drained by `update_state`
+pub enum QueueUpdate {
+    /// the playlist was read, (title, url) of every entry
+    Entries(Vec<(String, String)>),
+    Status(usize, QueueStatus),
+    Loaded(usize, Song),
+}
+
+const HISTORY_FILENAME: &str = "history.toml";
+const HISTORY_LIMIT: usize = 100;
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct History {
+    pub entries: Vec<HistoryEntry>,
+}
+
+impl History {
+    /// newest first, the oldest entries are dropped past `HISTORY_LIMIT`
+    pub fn push(&mut self, entry: HistoryEntry) {
+        self.entries.insert(0, entry);
+        self.entries.truncate(HISTORY_LIMIT);
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct HistoryEntry {
+    pub title: String,
+    pub artist: String,
+    pub source_url: String,
+    pub save_path: String,
+    /// unix seconds
+    pub timestamp: i64,
+}
 
+impl HistoryEntry {
+    fn new(song: &Song, save_path: &PathBuf) -> Self {
+        Self {
+            title: song.title.clone(),
+            artist: song.artist.clone(),
+            source_url: song.source_url.clone(),
+            save_path: save_path.to_string_lossy().to_string(),
+            timestamp: chrono::Local::now().timestamp(),
+        }
+    }
+    pub fn formatted_timestamp(&self) -> String {
+        chrono::Local
+            .timestamp_opt(self.timestamp, 0)
+            .single()
+            .map(|timestamp| timestamp.format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_default()
+    }
+}
+
+const SESSION_FILENAME: &str = "session.toml";
+const SESSION_AUTOSAVE_INTERVAL: Duration = Duration::from_secs(30);
+
+/// the edits on the loaded song, the audio and cover are queried again on restore
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct Session {
+    pub source_url: String,
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub album_artist: String,
+    pub composer: String,
+    pub track: String,
+    pub year: String,
+    pub genre: String,
+    pub lyrics: String,
+    pub save_path: PathBuf,
     pub separate_album: bool,
     pub separate_album_artist: bool,
-    pub seperate_composer: bool,
+    pub separate_composer: bool,
+}
+
+impl Session {
+    fn new(state: &DownloaderState) -> Self {
+        let song = &state.song;
+        Self {
+            source_url: song.source_url.clone(),
+            title: song.title.clone(),
+            artist: song.artist.clone(),
+            album: song.album.clone(),
+            album_artist: song.album_artist.clone(),
+            composer: song.composer.clone(),
+            track: song.track.clone(),
+            year: song.year.clone(),
+            genre: song.genre.clone(),
+            lyrics: song.lyrics.clone(),
+            save_path: state.save_path.clone(),
+            separate_album: song.separate_album,
+            separate_album_artist: song.separate_album_artist,
+            separate_composer: song.separate_composer,
+        }
+    }
+    fn apply(self, state: &mut DownloaderState) {
+        let song = &mut state.song;
+        song.title = self.title;
+        song.artist = self.artist;
+        song.album = self.album;
+        song.album_artist = self.album_artist;
+        song.composer = self.composer;
+        song.track = self.track;
+        song.year = self.year;
+        song.genre = self.genre;
+        song.lyrics = self.lyrics;
+        song.separate_album = self.separate_album;
+        song.separate_album_artist = self.separate_album_artist;
+        song.separate_composer = self.separate_composer;
+    }
+}
+
+fn init_session() -> Option<Session> {
+    fs::read_to_string(SESSION_FILENAME)
+        .ok()
+        .and_then(|toml_string| toml::from_str(&toml_string).ok())
+}
+
+fn init_history() -> History {
+    fs::read_to_string(HISTORY_FILENAME)
+        .ok()
+        .and_then(|toml_string| toml::from_str(&toml_string).ok())
+        .unwrap_or_default()
+}
+
+#[derive(Default)]
+pub struct BatchReport {
+    pub started: Option<Instant>,
+    pub finished: Option<Instant>,
+    pub entries: Vec<ReportEntry>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct ReportEntry {
+    pub source_url: String,
+    pub title: String,
+    pub bytes: usize,
+    pub elapsed_secs: f32,
+    pub error: Option<String>,
+}
+
+impl ReportEntry {
+    fn from_result(
+        source_url: String,
+        fallback_title: &str,
+        result: &Result<Song>,
+        started: Instant,
+    ) -> Self {
+        let (title, bytes, error) = match result {
+            Ok(song) => (song.title.clone(), song.audio_bytes.len(), None),
+            Err(error) => (fallback_title.to_string(), 0, Some(error.to_string())),
+        };
+        Self {
+            source_url,
+            title,
+            bytes,
+            elapsed_secs: started.elapsed().as_secs_f32(),
+            error,
+        }
+    }
+}
+
+impl BatchReport {
+    pub fn begin(&mut self) {
+        if self.started.is_none() {
+            self.started = Some(Instant::now());
+        }
+    }
+    pub fn record(&mut self, entry: ReportEntry) {
+        self.entries.push(entry);
+        self.finished = Some(Instant::now());
+    }
+    pub fn completed(&self) -> usize {
+        self.entries.iter().filter(|e| e.error.is_none()).count()
+    }
+    pub fn failed(&self) -> usize {
+        self.entries.iter().filter(|e| e.error.is_some()).count()
+    }
+    pub fn total_bytes(&self) -> usize {
+        self.entries.iter().map(|e| e.bytes).sum()
+    }
+    pub fn elapsed(&self) -> Duration {
+        match (self.started, self.finished) {
+            (Some(started), Some(finished)) => finished.duration_since(started),
+            (Some(started), None) => started.elapsed(),
+            _ => Duration::ZERO,
+        }
+    }
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(&json!({
+            "total": self.entries.len(),
+            "completed": self.completed(),
+            "failed": self.failed(),
+            "total_bytes": self.total_bytes(),
+            "elapsed_secs": self.elapsed().as_secs_f32(),
+            "entries": self.entries,
+        }))?)
+    }
+    pub fn to_csv(&self) -> String {
+        let escape = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+        let mut csv = String::from("source_url,title,bytes,elapsed_secs,status,error\n");
+        for entry in &self.entries {
+            csv.push_str(&format!(
+                "{},{},{},{:.2},{},{}\n",
+                escape(&entry.source_url),
+                escape(&entry.title),
+                entry.bytes,
+                entry.elapsed_secs,
+                if entry.error.is_some() { "failed" } else { "completed" },
+                escape(entry.error.as_deref().unwrap_or_default()),
+            ));
+        }
+        csv
+    }
+}
+
+#[derive(Default, Clone, Copy, PartialEq)]
+pub enum PreviewChannel {
+    #[default]
+    Stereo,
+    Left,
+    Right,
+}
+
+impl PreviewChannel {
+    pub fn next(&self) -> Self {
+        match self {
+            Self::Stereo => Self::Left,
+            Self::Left => Self::Right,
+            Self::Right => Self::Stereo,
+        }
+    }
+    /// only touches the data handed to kira, the song's own bytes are left alone
+    fn apply(&self, sound_data: StaticSoundData) -> StaticSoundData {
+        let select: fn(&Frame) -> f32 = match self {
+            Self::Stereo => return sound_data,
+            Self::Left => |f| f.left,
+            Self::Right => |f| f.right,
+        };
+        StaticSoundData {
+            frames: sound_data
+                .frames
+                .iter()
+                .map(|f| Frame::from_mono(select(f)))
+                .collect(),
+            ..sound_data
+        }
+    }
+}
+
+impl std::fmt::Display for PreviewChannel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Stereo => write!(f, "LR"),
+            Self::Left => write!(f, "L"),
+            Self::Right => write!(f, "R"),
+        }
+    }
 }
 
 trait Ready {
@@ -83,65 +508,222 @@ impl<T: Send> Ready for Option<Promise<T>> {
     }
 }
 
+const SCRUB_SEEK_INTERVAL: Duration = Duration::from_millis(50);
+/// in dBFS, anything above 0 would clip
+const PEAK_TARGET_RANGE: RangeInclusive<f32> = -60.0..=0.;
+/// typed offsets are clamped to this many dB either way, anything past it only clips or mutes
+pub const VOLUME_OFFSET_LIMIT: f32 = 30.;
+
 const PLAYBACK_TWEEN: Tween = Tween {
     duration: Duration::from_millis(200),
     start_time: kira::StartTime::Immediate,
     easing: kira::tween::Easing::Linear,
 };
 
-#[derive(Serialize, Deserialize, Default)]
+#[derive(Serialize, Deserialize)]
 pub struct Settings {
     pub default_save_directory: Option<String>,
 
     pub ffmpeg_path: Option<String>,
     pub ytdl_path: Option<String>,
+    pub audio_editor_path: Option<String>,
 
     pub playback_volume: f32,
+    pub fade_duration_ms: u64,
+    pub target_lufs: f32,
+    pub cache_waveform_peaks: bool,
+    pub audio_format: AudioFormat,
+    pub sample_format: SampleFormat,
+    pub bitrate: Bitrate,
+    pub keep_original_format: bool,
+    pub check_ytdl_update_on_startup: bool,
+    pub cookies_file: Option<String>,
+    pub cookies_from_browser: Option<String>,
+    pub proxy_url: Option<String>,
+    pub ytdl_format: String,
+    pub download_rate_limit: Option<String>,
+    pub window_size: Option<[f32; 2]>,
+    pub window_pos: Option<[f32; 2]>,
+    pub theme: Theme,
+    pub folder_structure: FolderStructure,
+    pub tag_provenance: bool,
+    pub cover_fit: CoverFit,
+    pub waveform_mode: WaveformMode,
+    pub max_concurrent_downloads: usize,
+    pub max_download_attempts: u32,
+    pub loudness_mode: LoudnessMode,
+    pub clean_titles: bool,
+    pub title_junk_patterns: Vec<String>,
+    pub queue_end: QueueEnd,
+    pub autoplay_queue: bool,
+    pub auto_advance_queue: bool,
 }
 
-fn init_settings() -> Result<Settings> {
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            default_save_directory: None,
+            ffmpeg_path: None,
+            ytdl_path: None,
+            audio_editor_path: None,
+            playback_volume: 0.,
+            fade_duration_ms: 0,
+            target_lufs: -14.,
+            cache_waveform_peaks: false,
+            audio_format: AudioFormat::default(),
+            sample_format: SampleFormat::default(),
+            bitrate: Bitrate::default(),
+            keep_original_format: false,
+            check_ytdl_update_on_startup: false,
+            cookies_file: None,
+            cookies_from_browser: None,
+            proxy_url: None,
+            ytdl_format: YT_DL_FORMAT_SELECTOR.to_string(),
+            download_rate_limit: None,
+            window_size: None,
+            window_pos: None,
+            theme: Theme::default(),
+            folder_structure: FolderStructure::default(),
+            tag_provenance: false,
+            cover_fit: CoverFit::default(),
+            waveform_mode: WaveformMode::default(),
+            max_concurrent_downloads: 3,
+            max_download_attempts: 3,
+            loudness_mode: LoudnessMode::default(),
+            clean_titles: false,
+            title_junk_patterns: DEFAULT_TITLE_JUNK_PATTERNS.map(String::from).to_vec(),
+            queue_end: QueueEnd::default(),
+            autoplay_queue: false,
+            auto_advance_queue: false,
+        }
+    }
+}
+
+impl Settings {
+    pub fn encode_options(&self) -> EncodeOptions {
+        EncodeOptions {
+            format: self.audio_format,
+            sample_format: self.sample_format,
+            bitrate: self.bitrate,
+            keep_original: self.keep_original_format,
+        }
+    }
+    /// the patterns to clean loaded titles with, if that's enabled
+    pub fn title_cleanup(&self) -> Option<Vec<String>> {
+        self.clean_titles.then(|| self.title_junk_patterns.clone())
+    }
+    pub fn ytdl_options(&self) -> YtdlOptions {
+        YtdlOptions {
+            cookies_file: self.cookies_file.clone(),
+            cookies_from_browser: self.cookies_from_browser.clone(),
+            proxy_url: self.proxy_url.clone().filter(|proxy_url| !proxy_url.trim().is_empty()),
+            format: self.ytdl_format.clone(),
+            rate_limit: self
+                .download_rate_limit
+                .as_ref()
+                .map(|rate_limit| rate_limit.trim().to_string())
+                .filter(|rate_limit| !rate_limit.is_empty()),
+            max_attempts: self.max_download_attempts.max(1),
+        }
+    }
+    /// points ffmpeg and yt-dl at the configured paths
+    pub fn set_commands(&self) {
+        set_command(DEFAULT_FFMPEG_COMMAND, self.ffmpeg_path.clone());
+        set_command(DEFAULT_YT_DL_COMMAND, self.ytdl_path.clone());
+    }
+    pub fn pipeline(&self) -> SongPipeline {
+        SongPipeline {
+            encode_options: self.encode_options(),
+            ytdl_options: self.ytdl_options(),
+            cache_waveform_peaks: self.cache_waveform_peaks,
+            title_cleanup: self.title_cleanup(),
+            folder_structure: self.folder_structure,
+            write_replaygain: self.loudness_mode == LoudnessMode::ReplayGain,
+            write_provenance: self.tag_provenance,
+        }
+    }
+    /// zero keeps kira's default, which is effectively instant
+    pub fn fade_tween(&self) -> Tween {
+        if self.fade_duration_ms == 0 {
+            Tween::default()
+        } else {
+            Tween {
+                duration: Duration::from_millis(self.fade_duration_ms),
+                ..Default::default()
+            }
+        }
+    }
+}
+
+pub fn init_settings() -> Result<Settings> {
     Ok(Figment::from(Serialized::defaults(Settings::default()))
         .merge(figment::providers::Toml::file(SETTINGS_FILENAME))
         .extract()?)
 }
 
-pub fn json_read(json: &Value, field: &str) -> String {
-    json.get(field)
-        .unwrap_or(&json!(""))
-        .to_string()
-        .replace("\"", "")
-}
-
 impl eframe::App for App {
-    fn update(&mut self, ctx: &Context, _frame: &mut eframe::Frame) {
+    fn update(&mut self, ctx: &Context, frame: &mut eframe::Frame) {
+        // kept up to date here since `on_exit` can't see the window
+        let window_info = &frame.info().window_info;
+        if !window_info.minimized {
+            self.settings.window_size = Some(window_info.size.into());
+            self.settings.window_pos = window_info.position.map(|position| position.into());
+        }
+        self.handle_dropped_files(ctx);
         interface::draw_root(self, ctx);
+        if self.quit_when_done && !self.is_song_loading() {
+            self.quit_confirmed = true;
+        }
+        if self.quit_confirmed {
+            frame.close();
+        }
         self.toasts.show(ctx);
         self.update_state(ctx);
         ctx.request_repaint();
     }
+    fn on_close_event(&mut self) -> bool {
+        if self.quit_confirmed || !self.is_song_loading() {
+            return true;
+        }
+        self.confirming_quit = true;
+        false
+    }
     fn on_exit(&mut self, _gl: Option<&eframe::glow::Context>) {
+        kill_running_commands();
         if let Err(_error) = (|| {
             let toml_string = toml::to_string(&self.settings)?;
             fs::write(SETTINGS_FILENAME, toml_string)?;
+            let toml_string = toml::to_string(&*self.history.lock())?;
+            fs::write(HISTORY_FILENAME, toml_string)?;
+            self.save_session()?;
             anyhow::Ok(())
         })() {}
     }
 }
 
 pub fn init() {
-    let window_options = eframe::NativeOptions {
-        initial_window_size: Some(iconst!(WINDOW_SIZE)),
-        resizable: false,
-        ..Default::default()
-    };
-
     let mut app = App::default();
     let settings = init_settings().expect("failed to initialize settings");
     app.settings = settings;
 
+    let window_options = eframe::NativeOptions {
+        initial_window_size: Some(
+            app.settings
+                .window_size
+                .map(Vec2::from)
+                .unwrap_or(iconst!(WINDOW_SIZE)),
+        ),
+        initial_window_pos: app.settings.window_pos.map(Pos2::from),
+        min_window_size: Some(iconst!(WINDOW_SIZE)),
+        ..Default::default()
+    };
+    app.history = Arc::new(Mutex::new(init_history()));
+    app.saved_session = init_session();
+
     app.read_config();
+    app.check_ytdl_version(app.settings.check_ytdl_update_on_startup);
 
-    app.audio_manager = AudioManager::<DefaultBackend>::new(AudioManagerSettings::default()).ok();
+    app.init_audio();
 
     let _ = eframe::run_native(
         env!("CARGO_PKG_NAME"),
@@ -149,83 +731,199 @@ pub fn init() {
         Box::new(|cc| {
             let ctx = &cc.egui_ctx;
             load_fonts(ctx);
-            load_style(ctx);
+            load_style(ctx, &app.settings.theme);
             Box::new(app)
         }),
     );
 }
 
-fn load_egui_image(ctx: &Context, name: &str, image: &DynamicImage) -> Result<TextureHandle> {
+fn load_egui_image(
+    ctx: &Context,
+    name: &str,
+    image: &DynamicImage,
+    cover_fit: CoverFit,
+) -> Result<TextureHandle> {
     let (w, h) = (image.width(), image.height());
-    let image_cropped = imageops::crop_imm(
-        image,
-        if h > w { 0 } else { (w - h) / 2 },
-        if w > h { 0 } else { (h - w) / 2 },
-        if h > w { w } else { h },
-        if w > h { h } else { w },
-    )
-    .to_image();
+    let image_square = match cover_fit {
+        CoverFit::Crop => imageops::crop_imm(
+            image,
+            if h > w { 0 } else { (w - h) / 2 },
+            if w > h { 0 } else { (h - w) / 2 },
+            if h > w { w } else { h },
+            if w > h { h } else { w },
+        )
+        .to_image(),
+        CoverFit::Pad => {
+            let side = w.max(h);
+            let mut canvas = RgbaImage::new(side, side);
+            imageops::overlay(
+                &mut canvas,
+                &image.to_rgba8(),
+                ((side - w) / 2) as i64,
+                ((side - h) / 2) as i64,
+            );
+            canvas
+        }
+    };
     let egui_image = ColorImage::from_rgba_unmultiplied(
         [
-            image_cropped.width() as usize,
-            image_cropped.height() as usize,
+            image_square.width() as usize,
+            image_square.height() as usize,
         ],
-        image_cropped.as_flat_samples().as_slice(),
+        image_square.as_flat_samples().as_slice(),
     );
     Ok(ctx.load_texture(name, egui_image, TextureOptions::default()))
 }
 
-pub fn tempfile(contents: &[u8]) -> Result<(NamedTempFile, String)> {
-    let mut tempfile = tempfile::NamedTempFile::new()?;
-    let path = tempfile.path().to_string_lossy().to_string();
-    tempfile.write(contents)?;
-    Ok((tempfile, path))
+/// reads `mm:ss`, `ss` or `h:mm:ss` into seconds, fractional seconds are allowed
+pub fn parse_timestamp(timestamp: &str) -> Option<f32> {
+    let timestamp = timestamp.trim();
+    if timestamp.is_empty() {
+        return None;
+    }
+    timestamp.split(':').try_fold(0., |seconds, component| {
+        let component = component.parse::<f32>().ok()?;
+        (component >= 0.).then_some(seconds * 60. + component)
+    })
+}
+
+/// where `seek_ratio` of the way through a song `duration` long is, in seconds
+pub fn seek_position(duration: Duration, seek_ratio: f32) -> f64 {
+    duration.as_secs_f64() * seek_ratio.clamp(0., 1.) as f64
 }
 
-pub fn remove_characters(s: &mut String, c: &[&str]) {
-    c.into_iter().for_each(|ss| {
-        *s = s.replace(ss, "");
-    });
+/// whole seconds are floored, so playback doesn't show a second before it's reached
+pub fn format_timestamp(seconds: f32) -> String {
+    let seconds = seconds.max(0.) as u32;
+    format!("{}:{:02}", seconds / 60, seconds % 60)
+}
+
+/// reads a typed volume offset like `-3`, `+2.5` or `4 dB`, before it's clamped
+pub fn parse_volume_offset(offset: &str) -> Option<f32> {
+    let offset = offset.trim();
+    let number = ["dB", "db", "DB", "Db"]
+        .into_iter()
+        .find_map(|suffix| offset.strip_suffix(suffix))
+        .unwrap_or(offset)
+        .trim();
+    number.parse::<f32>().ok().filter(|offset| offset.is_finite())
 }
 
 impl App {
+    /// playback stays unavailable until this succeeds, the rest of the app works without it
+    pub fn init_audio(&mut self) -> bool {
+        match AudioManager::<DefaultBackend>::new(AudioManagerSettings::default()) {
+            Ok(audio_manager) => {
+                self.audio_manager = Some(audio_manager);
+                true
+            }
+            Err(error) => {
+                self.toasts
+                    .warning(format!("no audio output, playback is unavailable: {error}"))
+                    .set_duration(None)
+                    .set_closable(true);
+                false
+            }
+        }
+    }
+    pub fn retry_audio_init(&mut self) {
+        if self.init_audio() {
+            self.toasts.success("audio output ready");
+        }
+    }
     pub fn start_song(&mut self) -> Result<()> {
         self.stop_current_playing_song()?;
         if let Some(audio_manager) = self.audio_manager.as_mut() {
+            self.downloader_state.song.ensure_audio_frames()?;
             if let Some(sound_data) = self.downloader_state.song.audio_frames.clone() {
+                let mut sound_data = self.downloader_state.preview_channel.apply(sound_data);
+                if self.downloader_state.repeat {
+                    sound_data = sound_data.with_modified_settings(|s| s.loop_region(..));
+                }
+                if self.settings.fade_duration_ms > 0 {
+                    let fade_tween = self.settings.fade_tween();
+                    sound_data = sound_data.with_modified_settings(|s| s.fade_in_tween(fade_tween));
+                }
                 let mut song_handle = audio_manager.play(sound_data)?;
-                song_handle.set_volume(self.settings.playback_volume as f64, PLAYBACK_TWEEN)?;
+                song_handle.set_volume(self.playback_volume(), PLAYBACK_TWEEN)?;
                 self.downloader_state.song_handle = Some(song_handle);
+                self.downloader_state.suppress_auto_advance = false;
             }
         } else {
             bail!("no sound device")
         }
         Ok(())
     }
+    /// muting leaves `Settings::playback_volume` alone, so it's restored on unmute and restart
+    fn playback_volume(&self) -> f64 {
+        if self.downloader_state.muted {
+            0.
+        } else {
+            self.settings.playback_volume as f64
+        }
+    }
     pub fn apply_playback_volume(&mut self) -> Result<()> {
+        let playback_volume = self.playback_volume();
         if let Some(current_song_handle) = self.downloader_state.song_handle.as_mut() {
-            current_song_handle
-                .set_volume(self.settings.playback_volume as f64, Tween::default())?;
+            current_song_handle.set_volume(playback_volume, Tween::default())?;
+        }
+        Ok(())
+    }
+    pub fn set_muted(&mut self, muted: bool) -> Result<()> {
+        self.downloader_state.muted = muted;
+        let playback_volume = self.playback_volume();
+        if let Some(current_song_handle) = self.downloader_state.song_handle.as_mut() {
+            current_song_handle.set_volume(playback_volume, PLAYBACK_TWEEN)?;
+        }
+        Ok(())
+    }
+
+    pub fn set_preview_channel(&mut self, preview_channel: PreviewChannel) -> Result<()> {
+        self.downloader_state.preview_channel = preview_channel;
+        let playing_position = self
+            .downloader_state
+            .song_handle
+            .as_ref()
+            .filter(|h| h.state() == kira::sound::PlaybackState::Playing)
+            .map(|h| h.position());
+        if let Some(position) = playing_position {
+            self.start_song()?;
+            if let Some(song_handle) = self.downloader_state.song_handle.as_mut() {
+                song_handle.seek_to(position)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// loops the whole song, the playing handle is updated in place
+    pub fn set_repeat(&mut self, repeat: bool) -> Result<()> {
+        self.downloader_state.repeat = repeat;
+        if let Some(song_handle) = self.downloader_state.song_handle.as_mut() {
+            if repeat {
+                song_handle.set_loop_region(..)?;
+            } else {
+                song_handle.set_loop_region(None)?;
+            }
         }
         Ok(())
     }
 
     pub fn stop_current_playing_song(&mut self) -> Result<()> {
+        self.downloader_state.suppress_auto_advance = true;
         if let Some(current_song_handle) = self.downloader_state.song_handle.as_mut() {
-            current_song_handle.stop(Tween::default())?;
+            current_song_handle.stop(self.settings.fade_tween())?;
         }
         Ok(())
     }
 
     pub fn toggle_song_playback(&mut self) -> Result<()> {
+        let fade_tween = self.settings.fade_tween();
         let mut do_start = false;
         if let Some(current_song_handle) = self.downloader_state.song_handle.as_mut() {
             match current_song_handle.state() {
-                kira::sound::PlaybackState::Playing => current_song_handle.pause(PLAYBACK_TWEEN)?,
-                kira::sound::PlaybackState::Pausing => {
-                    current_song_handle.resume(PLAYBACK_TWEEN)?
-                }
-                kira::sound::PlaybackState::Paused => current_song_handle.resume(PLAYBACK_TWEEN)?,
+                kira::sound::PlaybackState::Playing => current_song_handle.pause(fade_tween)?,
+                kira::sound::PlaybackState::Pausing => current_song_handle.resume(fade_tween)?,
+                kira::sound::PlaybackState::Paused => current_song_handle.resume(fade_tween)?,
                 kira::sound::PlaybackState::Stopping => do_start = true,
                 kira::sound::PlaybackState::Stopped => do_start = true,
             }
@@ -239,6 +937,7 @@ impl App {
     }
 
     pub fn seek_song(&mut self, seek_ratio: f32) -> Result<()> {
+        self.downloader_state.song.ensure_audio_frames()?;
         let total_duration = self
             .downloader_state
             .song
@@ -247,8 +946,39 @@ impl App {
             .map(|s| s.duration())
             .context("no song data")?;
         if let Some(current_song_handle) = self.downloader_state.song_handle.as_mut() {
-            let target_position = total_duration.as_secs() as f32 * seek_ratio;
-            current_song_handle.seek_to(target_position as f64)?;
+            current_song_handle.seek_to(seek_position(total_duration, seek_ratio))?;
+        }
+        Ok(())
+    }
+
+    /// seeks while the waveform is dragged, at most once per `SCRUB_SEEK_INTERVAL`
+    pub fn scrub_song(&mut self, seek_ratio: f32) -> Result<()> {
+        self.downloader_state.scrub_ratio = Some(seek_ratio);
+        let due = self
+            .downloader_state
+            .last_scrub_seek
+            .map_or(true, |last_seek| last_seek.elapsed() >= SCRUB_SEEK_INTERVAL);
+        if due {
+            self.downloader_state.last_scrub_seek = Some(Instant::now());
+            self.seek_song(seek_ratio)?;
+        }
+        Ok(())
+    }
+
+    /// seeks relative to the current position, clamped to the song
+    pub fn seek_song_by(&mut self, offset_secs: f64) -> Result<()> {
+        self.downloader_state.song.ensure_audio_frames()?;
+        let total_duration = self
+            .downloader_state
+            .song
+            .audio_frames
+            .as_ref()
+            .map(|s| s.duration().as_secs_f64())
+            .context("no song data")?;
+        if let Some(current_song_handle) = self.downloader_state.song_handle.as_mut() {
+            let target_position =
+                (current_song_handle.position() + offset_secs).clamp(0., total_duration);
+            current_song_handle.seek_to(target_position)?;
         }
         Ok(())
     }
@@ -262,47 +992,419 @@ impl App {
                 self.downloader_state
                     .song_handle
                     .as_ref()
-                    .map(|s| s.position() as f32 / d.duration().as_secs() as f32)
+                    .map(|s| (s.position() / d.duration().as_secs_f64()) as f32)
             })
     }
 
+    /// moves on to the next finished entry once the current song has played to its end
+    fn auto_advance(&mut self) {
+        if !self.settings.auto_advance_queue
+            || self.downloader_state.suppress_auto_advance
+            || self.downloader_state.loading_song.is_some()
+        {
+            return;
+        }
+        let Some(song_handle) = self.downloader_state.song_handle.as_ref() else {
+            return;
+        };
+        let duration = self.downloader_state.song.duration().unwrap_or_default() as f64;
+        let finished = song_handle.state() == kira::sound::PlaybackState::Stopped
+            && duration - song_handle.position() < AUTO_ADVANCE_TOLERANCE_SECS;
+        if !finished {
+            return;
+        }
+        // only once per song, even if there's nothing to advance to
+        self.downloader_state.suppress_auto_advance = true;
+        if let Some(index) = self.neighboring_queue_entry(1) {
+            self.select_queue_entry(index);
+            if let Err(error) = self.start_song() {
+                self.toasts.error(format!("failed playing song: {error}"));
+            }
+        }
+    }
+
     fn update_state(&mut self, _ctx: &Context) {
+        self.auto_advance();
+        if let Some(progress_receiver) = self.downloader_state.progress_receiver.as_ref() {
+            while let Ok(progress) = progress_receiver.try_recv() {
+                self.downloader_state.progress = progress;
+            }
+        }
+        if !self.is_song_loading() {
+            self.downloader_state.progress = None;
+            self.downloader_state.progress_receiver = None;
+        }
         if self.downloader_state.loading_song.is_ready() {
             let loaded_song = self.downloader_state.loading_song.unwrap_and_take();
+            let restoring_session = self.restoring_session.take();
             if let Ok(song) = loaded_song {
                 self.downloader_state.song = song;
+                if let Some(session) = restoring_session {
+                    session.apply(&mut self.downloader_state);
+                }
+            }
+        }
+        self.autosave_session();
+        if let Some(queue_receiver) = self.downloader_state.queue_receiver.clone() {
+            let state = &mut self.downloader_state;
+            while let Ok(update) = queue_receiver.try_recv() {
+                match update {
+                    QueueUpdate::Entries(entries) => {
+                        state.queue = entries
+                            .into_iter()
+                            .map(|(title, source_url)| QueueEntry {
+                                title,
+                                status: QueueStatus::Queued,
+                                song: Song {
+                                    source_url,
+                                    ..Default::default()
+                                },
+                            })
+                            .collect();
+                        state.queue_index = 0;
+                        state.song = std::mem::take(&mut state.queue[0].song);
+                        state.song_origin = Origin::from_link(&state.song.source_url);
+                    }
+                    QueueUpdate::Status(index, status) => state.queue[index].status = status,
+                    QueueUpdate::Loaded(index, mut song) => {
+                        state.queue[index].status = QueueStatus::Done;
+                        if let Some(batch_tags) = &state.applied_batch_tags {
+                            batch_tags.apply(&mut song);
+                        }
+                        if index == state.queue_index {
+                            state.song = song;
+                        } else {
+                            state.queue[index].song = song;
+                        }
+                    }
+                }
+            }
+            // the bar shows how much of the playlist is through, workers don't report their own
+            let finished = state
+                .queue
+                .iter()
+                .filter(|entry| matches!(entry.status, QueueStatus::Done | QueueStatus::Failed(_)))
+                .count();
+            if !state.queue.is_empty() {
+                state.progress = Some(finished as f32 / state.queue.len() as f32);
+            }
+        }
+        if self.downloader_state.loading_queue.is_ready() {
+            let _ = self.downloader_state.loading_queue.unwrap_and_take();
+            self.downloader_state.queue_receiver = None;
+        }
+        if self.downloader_state.looking_up_matches.is_ready() {
+            if let Ok(candidates) = self.downloader_state.looking_up_matches.unwrap_and_take() {
+                self.downloader_state.match_candidates = candidates;
+            }
+        }
+        if self.loading_ytdl_info.is_ready() {
+            match self.loading_ytdl_info.unwrap_and_take() {
+                Ok(ytdl_info) => {
+                    if ytdl_info.is_outdated() {
+                        self.toasts.info(format!(
+                            "yt-dl update available: {}",
+                            ytdl_info.latest_version.as_deref().unwrap_or_default()
+                        ));
+                    }
+                    self.ytdl_info = Some(ytdl_info);
+                }
+                Err(_) => self.ytdl_info = None,
+            }
+        }
+        if self.checking_commands.is_ready() {
+            self.command_checks = self.checking_commands.unwrap_and_take().into_iter().collect();
+        }
+        if self.updating_ytdl.is_ready() {
+            match self.updating_ytdl.unwrap_and_take() {
+                Ok(message) => {
+                    self.toasts.success(message);
+                    self.check_ytdl_version(false);
+                }
+                Err(error) => {
+                    self.toasts.error(format!("failed updating yt-dl: {error}"));
+                }
             }
         }
     }
+    pub fn check_ytdl_version(&mut self, check_latest: bool) {
+        let proxy_url = self.settings.ytdl_options().proxy_url;
+        self.loading_ytdl_info = Some(Promise::spawn_thread("check_ytdl", move || {
+            let latest_version = check_latest
+                .then(|| latest_ytdl_version(proxy_url.as_deref()).ok())
+                .flatten();
+            Ok(YtdlInfo {
+                installed_version: check_command(DEFAULT_YT_DL_COMMAND)?,
+                latest_version,
+            })
+        }));
+    }
+    pub fn update_ytdl(&mut self) {
+        self.toasts.info("updating yt-dl...");
+        self.updating_ytdl = Some(Promise::spawn_thread("update_ytdl", update_ytdl));
+    }
     pub fn read_config(&mut self) {
         if let Some(default_save_directory) = self.settings.default_save_directory.as_ref() {
             self.downloader_state.save_path = PathBuf::from(default_save_directory);
         }
 
-        set_command(DEFAULT_FFMPEG_COMMAND, self.settings.ffmpeg_path.clone());
-        set_command(DEFAULT_YT_DL_COMMAND, self.settings.ytdl_path.clone());
+        self.settings.set_commands();
+        self.check_commands();
+    }
+    pub fn check_commands(&mut self) {
+        self.checking_commands = Some(Promise::spawn_thread("check_commands", || {
+            [DEFAULT_FFMPEG_COMMAND, DEFAULT_YT_DL_COMMAND]
+                .into_iter()
+                .map(|name| (name, check_command(name)))
+                .collect()
+        }));
+    }
+    pub fn paste_query_url(&mut self) {
+        // egui can only paste into a focused text edit, so the clipboard is read directly
+        let mut pasted = arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.get_text())
+            .unwrap_or_default()
+            .trim()
+            .to_string();
+        remove_characters(&mut pasted, &["\""]);
+
+        let song_origin = Origin::from_link(&pasted);
+        if song_origin == Origin::Unknown {
+            self.toasts.info("clipboard doesn't contain a url or path");
+            return;
+        }
+        self.downloader_state.song.source_url = pasted;
+        self.downloader_state.song_origin = song_origin;
+    }
+    /// writes the current session, or clears the saved one if nothing is loaded
+    fn save_session(&mut self) -> Result<()> {
+        // the old session is kept around until it's answered and restored
+        if self.saved_session.is_some() || self.restoring_session.is_some() {
+            return Ok(());
+        }
+        let session = Session::new(&self.downloader_state);
+        if self.is_song_loaded() && !session.source_url.is_empty() {
+            fs::write(SESSION_FILENAME, toml::to_string(&session)?)?;
+        } else if fs::metadata(SESSION_FILENAME).is_ok() {
+            fs::remove_file(SESSION_FILENAME)?;
+        }
+        self.last_session_save = Some((Instant::now(), session));
+        Ok(())
+    }
+    /// saves the session every so often when it changed, since `on_exit` doesn't run on a crash
+    fn autosave_session(&mut self) {
+        let is_due = self
+            .last_session_save
+            .as_ref()
+            .map_or(true, |(saved_at, _)| saved_at.elapsed() >= SESSION_AUTOSAVE_INTERVAL);
+        if !is_due {
+            return;
+        }
+        let session = Session::new(&self.downloader_state);
+        if let Some((saved_at, last_session)) = self.last_session_save.as_mut() {
+            if *last_session == session {
+                *saved_at = Instant::now();
+                return;
+            }
+        }
+        let _ = self.save_session();
+    }
+    pub fn restore_session(&mut self, ctx: &Context) {
+        let Some(session) = self.saved_session.take() else {
+            return;
+        };
+        let song_origin = Origin::from_link(&session.source_url);
+        if song_origin == Origin::Local && !PathBuf::from(&session.source_url).exists() {
+            self.toasts.error(format!("{} no longer exists", session.source_url));
+            return;
+        }
+        self.downloader_state.song.source_url = session.source_url.clone();
+        self.downloader_state.song_origin = song_origin;
+        self.downloader_state.save_path = session.save_path.clone();
+        self.restoring_session = Some(session);
+        self.query(ctx);
+    }
+    pub fn discard_session(&mut self) {
+        self.saved_session = None;
+        let _ = self.save_session();
+    }
+    /// loads the first dropped audio file as a local song
+    fn handle_dropped_files(&mut self, ctx: &Context) {
+        let dropped_paths: Vec<PathBuf> = ctx.input(|i| {
+            i.raw
+                .dropped_files
+                .iter()
+                .filter_map(|file| file.path.clone())
+                .collect()
+        });
+        if dropped_paths.is_empty() {
+            return;
+        }
+        if self.is_song_loading() {
+            self.toasts.info("wait for the current query to finish");
+            return;
+        }
+        let Some(audio_path) = dropped_paths.iter().find(|path| is_audio_path(path)) else {
+            self.toasts.info("not an audio file");
+            return;
+        };
+        if dropped_paths.len() > 1 {
+            self.toasts
+                .info(format!("only loading the first file, ignored {}", dropped_paths.len() - 1));
+        }
+
+        self.downloader_state.song.source_url = audio_path.to_string_lossy().to_string();
+        self.downloader_state.song_origin = Origin::Local;
+        self.current_page = InterfacePage::Downloader;
+        self.query(ctx);
+    }
+    /// the cover texture of `DownloaderState::song`, made the first time it's drawn
+    pub fn cover_texture(&mut self, ctx: &Context) -> Option<TextureHandle> {
+        let state = &mut self.downloader_state;
+        let cover_fit = self.settings.cover_fit;
+        let cover_bytes = &state.song.cover_bytes;
+        if cover_bytes.is_empty() {
+            state.cover_texture = None;
+            return None;
+        }
+        let is_current = state.cover_texture.as_ref().is_some_and(|cover_texture| {
+            Arc::ptr_eq(&cover_texture.cover_bytes, cover_bytes)
+                && cover_texture.cover_fit == cover_fit
+        });
+        if !is_current {
+            let texture_handle = image::load_from_memory(cover_bytes).ok().and_then(|image| {
+                load_egui_image(ctx, &state.song.title, &image, cover_fit).ok()
+            });
+            state.cover_texture = Some(CoverTexture {
+                cover_bytes: cover_bytes.clone(),
+                cover_fit,
+                texture_handle,
+            });
+        }
+        state.cover_texture.as_ref()?.texture_handle.clone()
     }
     pub fn is_song_loaded(&self) -> bool {
         !self.downloader_state.song.audio_bytes.is_empty()
     }
     pub fn is_song_loading(&self) -> bool {
         self.downloader_state.loading_song.is_some()
+            || self.downloader_state.loading_queue.is_some()
     }
     pub fn apply_volume_offset(&mut self) {
+        let Some(offset) = parse_volume_offset(&self.downloader_state.volume_offset) else {
+            self.toasts.error("volume offset should be a number of dB");
+            return;
+        };
+        let offset = offset.clamp(-VOLUME_OFFSET_LIMIT, VOLUME_OFFSET_LIMIT);
+        self.downloader_state.volume_offset = format!("{offset:.2}");
+        self.offset_volume(offset);
+    }
+    /// offsets the volume so the measured peak lands on `target_peak`
+    pub fn apply_target_peak(&mut self) {
+        let target_peak = match self.downloader_state.target_peak.trim().parse::<f32>() {
+            Ok(target_peak) if PEAK_TARGET_RANGE.contains(&target_peak) => target_peak,
+            _ => {
+                self.toasts.error(format!(
+                    "target peak should be between {} and {} dB",
+                    PEAK_TARGET_RANGE.start(),
+                    PEAK_TARGET_RANGE.end()
+                ));
+                return;
+            }
+        };
+        let needed_offset = target_peak - self.downloader_state.song.peak_volume;
+        let offset = needed_offset.clamp(-VOLUME_OFFSET_LIMIT, VOLUME_OFFSET_LIMIT);
+        if offset != needed_offset {
+            self.toasts.warning(format!(
+                "reaching the target peak needs {needed_offset:+.2} dB, limited to {offset:+.2} dB"
+            ));
+        }
+        self.downloader_state.volume_offset = format!("{offset:.2}");
+        self.offset_volume(offset);
+    }
+    fn offset_volume(&mut self, offset: f32) {
         let mut song = self.downloader_state.song.clone();
-        let offset = self.downloader_state.volume_offset.parse::<f32>().unwrap();
         let toast = self.toasts.info("setting volume...").create_channel();
         let _ = self.stop_current_playing_song();
-        self.downloader_state.loading_song = Some(Promise::spawn_thread("save_song", move || {
+        self.downloader_state.loading_song = Some(self.spawn_cancellable("save_song", move || {
             if let Err(error) = (|| {
                 song.apply_volume_offset(offset)?;
+                toast.send(
+                    ToastUpdate::caption(format!("applied {offset:+.2} dB"))
+                        .with_level(egui_notify::ToastLevel::Success)
+                        .with_fallback_options(ToastOptions::default()),
+                )?;
                 anyhow::Ok(())
             })() {
+                toast.send(failure_update(&error))?;
+                return Err(error);
+            }
+            Ok(song)
+        }));
+    }
+    /// measures the volume again without reloading, e.g. after the bytes were edited
+    pub fn remeasure_volume(&mut self) {
+        let mut song = self.downloader_state.song.clone();
+        let toast = self.toasts.info("measuring volume...").create_channel();
+        self.downloader_state.loading_song = Some(self.spawn_cancellable("measure_volume", move || {
+            if let Err(error) = (|| {
+                song.update_current_volume()?;
                 toast.send(
-                    ToastUpdate::caption(format!("failed: {error}"))
-                        .with_fallback_options(ToastOptions::default())
-                        .with_level(egui_notify::ToastLevel::Error),
+                    ToastUpdate::caption(format!("volume is {}dB", song.volume))
+                        .with_level(egui_notify::ToastLevel::Success)
+                        .with_fallback_options(ToastOptions::default()),
+                )?;
+                anyhow::Ok(())
+            })() {
+                toast.send(failure_update(&error))?;
+                return Err(error);
+            }
+            Ok(song)
+        }));
+    }
+    pub fn normalize_loudness(&mut self) {
+        let mut song = self.downloader_state.song.clone();
+        let target_lufs = self.settings.target_lufs;
+        let toast = self.toasts.info("normalizing loudness...").create_channel();
+        let _ = self.stop_current_playing_song();
+        self.downloader_state.loading_song = Some(self.spawn_cancellable("normalize_song", move || {
+            if let Err(error) = (|| {
+                song.normalize_loudness(target_lufs)?;
+                toast.send(
+                    ToastUpdate::caption(format!("normalized to {target_lufs} LUFS"))
+                        .with_level(egui_notify::ToastLevel::Success)
+                        .with_fallback_options(ToastOptions::default()),
+                )?;
+                anyhow::Ok(())
+            })() {
+                toast.send(failure_update(&error))?;
+                return Err(error);
+            }
+            Ok(song)
+        }));
+    }
+    pub fn apply_trim(&mut self) {
+        let (Some(start), Some(end)) = (
+            parse_timestamp(&self.downloader_state.trim_start),
+            parse_timestamp(&self.downloader_state.trim_end),
+        ) else {
+            self.toasts.error("trim times should look like mm:ss");
+            return;
+        };
+        let mut song = self.downloader_state.song.clone();
+        let toast = self.toasts.info("trimming...").create_channel();
+        let _ = self.stop_current_playing_song();
+        self.downloader_state.loading_song = Some(self.spawn_cancellable("trim_song", move || {
+            if let Err(error) = (|| {
+                song.apply_trim(start, end)?;
+                toast.send(
+                    ToastUpdate::caption("trimmed")
+                        .with_level(egui_notify::ToastLevel::Success)
+                        .with_fallback_options(ToastOptions::default()),
                 )?;
+                anyhow::Ok(())
+            })() {
+                toast.send(failure_update(&error))?;
                 return Err(error);
             }
             Ok(song)
@@ -311,13 +1413,15 @@ impl App {
     pub fn save(&mut self) {
         let mut song = self.downloader_state.song.clone();
         let save_path = self.downloader_state.save_path.clone();
+        let history = self.history.clone();
+        let pipeline = self.settings.pipeline();
+        let last_saved_path = self.downloader_state.last_saved_path.clone();
         let toast = self.toasts.info("initializing...").create_channel();
-        self.downloader_state.loading_song = Some(Promise::spawn_thread("save_song", move || {
+        self.downloader_state.loading_song = Some(self.spawn_cancellable("save_song", move || {
             if let Err(error) = (|| {
-                toast.send(ToastUpdate::caption("updating song metadata..."))?;
-                song.update_bytes_from_metadata()?;
-                toast.send(ToastUpdate::caption("writing song to disk..."))?;
-                song.write_to_disk(&save_path)?;
+                let saved_path = pipeline.save(&mut song, &save_path, &toast)?;
+                history.lock().push(HistoryEntry::new(&song, &saved_path));
+                *last_saved_path.lock() = Some(saved_path);
                 toast.send(
                     ToastUpdate::caption("saved")
                         .with_level(egui_notify::ToastLevel::Success)
@@ -325,34 +1429,145 @@ impl App {
                 )?;
                 anyhow::Ok(())
             })() {
-                toast.send(
-                    ToastUpdate::caption(format!("failed: {error}"))
-                        .with_fallback_options(ToastOptions::default())
-                        .with_level(egui_notify::ToastLevel::Error),
-                )?;
+                toast.send(failure_update(&error))?;
                 return Err(error);
             }
             Ok(song)
         }));
     }
-    pub fn set_cover_by_path(&mut self, ctx: &Context, path: PathBuf) {
-        let toast: crossbeam_channel::Sender<ToastUpdate> =
-            self.toasts.info("loading cover...").create_channel();
-        let ctx_clone = ctx.clone();
+    pub fn reveal_last_saved(&mut self) {
+        let Some(saved_path) = self.downloader_state.last_saved_path.lock().clone() else {
+            return;
+        };
+        if !saved_path.exists() {
+            self.toasts.info("the saved file was moved or deleted");
+            return;
+        }
+        if let Err(error) = reveal_in_file_browser(&saved_path) {
+            self.toasts.error(format!("couldn't open folder: {error}"));
+        }
+    }
+    pub fn export_report(&mut self, path: PathBuf) {
+        if let Err(error) = (|| {
+            let report = self.report.lock();
+            let contents = if path.extension().is_some_and(|ext| ext == "json") {
+                report.to_json()?
+            } else {
+                report.to_csv()
+            };
+            fs::write(&path, contents)?;
+            anyhow::Ok(())
+        })() {
+            self.toasts.error(format!("failed exporting report: {error}"));
+        } else {
+            self.toasts.success("exported report");
+        }
+    }
+    pub fn export_settings(&mut self, path: PathBuf) {
+        if let Err(error) = (|| {
+            fs::write(&path, toml::to_string(&self.settings)?)?;
+            anyhow::Ok(())
+        })() {
+            self.toasts.error(format!("failed exporting settings: {error}"));
+        } else {
+            self.toasts.success("exported settings");
+        }
+    }
+    /// merged over the current settings, so a partial file only changes the fields it has
+    pub fn import_settings(&mut self, ctx: &Context, path: PathBuf) {
+        match Figment::from(Serialized::defaults(&self.settings))
+            .merge(figment::providers::Toml::file(path))
+            .extract::<Settings>()
+        {
+            Ok(settings) => {
+                self.settings = settings;
+                load_style(ctx, &self.settings.theme);
+                self.read_config();
+                self.toasts.success("imported settings");
+            }
+            Err(error) => {
+                self.toasts.error(format!("failed importing settings: {error}"));
+            }
+        }
+    }
+    pub fn edit_in_external_editor(&mut self) {
+        let Some(editor_path) = self
+            .settings
+            .audio_editor_path
+            .clone()
+            .filter(|path| !path.is_empty())
+        else {
+            self.toasts.info("set an external audio editor in settings first");
+            self.current_page = InterfacePage::Settings;
+            return;
+        };
         let mut song = self.downloader_state.song.clone();
-        self.downloader_state.loading_song = Some(Promise::spawn_thread("query_song", move || {
+        let toast = self.toasts.info("opening editor...").create_channel();
+        let _ = self.stop_current_playing_song();
+        self.downloader_state.loading_song = Some(self.spawn_cancellable("edit_song", move || {
             if let Err(error) = (|| {
-                let mut cover_bytes = fs::read(path)?;
+                let extension = song.encode_options.format.extension();
+                let (audio_tfile, audio_tfilepath) =
+                    tempfile_with_suffix(&song.audio_bytes, extension)?;
+                // close our handle so the editor is free to overwrite the file
+                let audio_tpath = audio_tfile.into_temp_path();
 
-                if !cover_bytes.is_empty() {
-                    let image = image::load_from_memory(&cover_bytes)?;
-                    let cover_texture_handle = load_egui_image(&ctx_clone, &song.title, &image)?;
-                    image.write_to(&mut Cursor::new(&mut cover_bytes), image::ImageFormat::Jpeg)?;
-
-                    song.cover_texture_handle = Some(cover_texture_handle);
-                    song.cover_bytes = cover_bytes;
+                toast.send(ToastUpdate::caption(
+                    "waiting for the edit to be saved, cancel to stop...",
+                ))?;
+                if open_in_editor(&editor_path, &audio_tfilepath)? {
+                    toast.send(ToastUpdate::caption("reloading edited audio..."))?;
+                    let edited_audio_bytes = fs::read(&audio_tpath)?;
+                    if edited_audio_bytes.is_empty() {
+                        bail!("edited file is empty")
+                    }
+                    song.audio_bytes = edited_audio_bytes.into();
+                    song.update_audio_frames()?;
+                    song.update_current_volume()?;
+                    song.update_audio_info()?;
+                    toast.send(
+                        ToastUpdate::caption("reloaded edited audio")
+                            .with_level(egui_notify::ToastLevel::Success)
+                            .with_fallback_options(ToastOptions::default()),
+                    )?;
+                } else {
+                    toast.send(
+                        ToastUpdate::caption("no changes from editor")
+                            .with_fallback_options(ToastOptions::default()),
+                    )?;
                 }
+                anyhow::Ok(())
+            })() {
+                toast.send(failure_update(&error))?;
+                return Err(error);
+            }
+            Ok(song)
+        }));
+    }
+    pub fn set_lyrics_by_path(&mut self, path: PathBuf) {
+        match fs::read_to_string(path) {
+            Ok(lyrics) => {
+                self.downloader_state.song.lyrics = lyrics;
+                self.toasts.success("lyrics loaded");
+            }
+            Err(error) => {
+                self.toasts.error(format!("failed loading lyrics: {error}"));
+            }
+        }
+    }
+    pub fn set_cover_by_path(&mut self, path: PathBuf) {
+        let toast: Sender<ToastUpdate> = self.toasts.info("loading cover...").create_channel();
+        let mut song = self.downloader_state.song.clone();
+        self.downloader_state.loading_song = Some(self.spawn_cancellable("set_cover", move || {
+            if let Err(error) = (|| {
+                let image_bytes = fs::read(path)?;
+                set_cover_from_image(&mut song, &image_bytes)?;
 
+                toast.send(
+                    ToastUpdate::caption("cover updated")
+                        .with_level(egui_notify::ToastLevel::Success)
+                        .with_fallback_options(ToastOptions::default()),
+                )?;
                 anyhow::Ok(())
             })() {
                 toast.send(
@@ -365,105 +1580,407 @@ impl App {
             Ok(song)
         }));
     }
+    pub fn lookup_matches(&mut self) {
+        let song = &self.downloader_state.song;
+        let (artist, title) = (song.artist.trim().to_string(), song.title.trim().to_string());
+        if artist.is_empty() || title.is_empty() {
+            self.toasts.info("fill in the title and artist first");
+            return;
+        }
+        let proxy_url = self.settings.ytdl_options().proxy_url;
+        let toast = self.toasts.info("looking up matches...").create_channel();
+        self.downloader_state.looking_up_matches =
+            Some(Promise::spawn_thread("lookup_matches", move || {
+                match musicbrainz::lookup(&artist, &title, proxy_url.as_deref()) {
+                    Ok(candidates) => {
+                        toast.send(
+                            ToastUpdate::caption(format!("found {} matches", candidates.len()))
+                                .with_level(egui_notify::ToastLevel::Success)
+                                .with_fallback_options(ToastOptions::default()),
+                        )?;
+                        Ok(candidates)
+                    }
+                    Err(error) => {
+                        toast.send(failure_update(&error))?;
+                        Err(error)
+                    }
+                }
+            }));
+    }
+    /// fills the song from a musicbrainz match, fields the user already set are
+    /// only replaced with `overwrite_matched_tags`
+    pub fn apply_match(&mut self, index: usize) {
+        let Some(candidate) = self.downloader_state.match_candidates.get(index).cloned() else {
+            return;
+        };
+        let overwrite = self.downloader_state.overwrite_matched_tags;
+        // the album only mirrors the title until it's set separately
+        let replace_album = overwrite || !self.downloader_state.song.separate_album;
+        if replace_album && !candidate.album.is_empty() {
+            self.downloader_state.song.separate_album = true;
+        }
+        let proxy_url = self.settings.ytdl_options().proxy_url;
+        let toast = self.toasts.info("applying match...").create_channel();
+        let mut song = self.downloader_state.song.clone();
+        self.downloader_state.loading_song = Some(self.spawn_cancellable("apply_match", move || {
+            if let Err(error) = (|| {
+                let fill = |field: &mut String, value: &str, replace: bool| {
+                    if !value.is_empty() && (replace || field.trim().is_empty()) {
+                        *field = value.to_string();
+                    }
+                };
+                fill(&mut song.title, &candidate.title, overwrite);
+                fill(&mut song.artist, &candidate.artist, overwrite);
+                fill(&mut song.album, &candidate.album, replace_album);
+                fill(&mut song.year, &candidate.year, overwrite);
+                fill(&mut song.track, &candidate.track, overwrite);
+
+                if overwrite || song.cover_bytes.is_empty() {
+                    toast.send(ToastUpdate::caption("downloading cover..."))?;
+                    let image_bytes =
+                        musicbrainz::cover(&candidate.release_id, proxy_url.as_deref())?;
+                    set_cover_from_image(&mut song, &image_bytes)?;
+                }
+
+                toast.send(
+                    ToastUpdate::caption("match applied")
+                        .with_level(egui_notify::ToastLevel::Success)
+                        .with_fallback_options(ToastOptions::default()),
+                )?;
+                anyhow::Ok(())
+            })() {
+                toast.send(failure_update(&error))?;
+                return Err(error);
+            }
+            Ok(song)
+        }));
+    }
+    pub fn query_source(&mut self, ctx: &Context, source_url: String) {
+        if self.is_song_loading() {
+            self.toasts.info("wait for the current query to finish");
+            return;
+        }
+        self.downloader_state.song_origin = Origin::from_link(&source_url);
+        self.downloader_state.song.source_url = source_url;
+        self.current_page = InterfacePage::Downloader;
+        self.query(ctx);
+    }
     pub fn query(&mut self, ctx: &Context) {
-        let ctx_clone = ctx.clone();
+        if is_playlist_link(&self.downloader_state.song.source_url) {
+            return self.query_playlist(ctx);
+        }
         let query_url = self.downloader_state.song.source_url.clone();
         let song_origin = self.downloader_state.song_origin;
+        let pipeline = self.settings.pipeline();
         let toast = self.toasts.info("initializing...").create_channel();
+        let report = self.report.clone();
+        let (progress_sender, progress_receiver) = crossbeam_channel::unbounded();
+        self.downloader_state.progress_receiver = Some(progress_receiver);
 
         let _ = self.stop_current_playing_song();
+        self.downloader_state.queue.clear();
+        self.downloader_state.match_candidates.clear();
 
-        self.downloader_state.loading_song = Some(Promise::spawn_thread("query_song", move || {
-            let mut song: Song = Song::default();
-            if let Err(error) = (|| {
-                if song_origin == Origin::Local {
-                    toast.send(ToastUpdate::caption("reading..."))?;
-                    let audio_bytes = fs::read(&query_url)?;
-
-                    if audio_bytes.is_empty() {
-                        bail!("read error")
-                    }
+        self.downloader_state.loading_song = Some(self.spawn_cancellable("query_song", move || {
+            let started = Instant::now();
+            // a single song is a report of its own, not another entry in the last batch
+            *report.lock() = BatchReport::default();
+            report.lock().begin();
+            let status = LoadingStatus {
+                toast,
+                progress: progress_sender,
+                prefix: String::new(),
+            };
+            let loaded_song = if song_origin == Origin::Local {
+                pipeline.load_local(query_url.clone(), &status)
+            } else {
+                pipeline.load_remote(query_url.clone(), &status)
+            };
+            report
+                .lock()
+                .record(ReportEntry::from_result(query_url, "", &loaded_song, started));
 
-                    toast.send(ToastUpdate::caption("converting audio..."))?;
-                    let converted_audio_bytes = convert_audio(&audio_bytes)?;
-
-                    if converted_audio_bytes.is_empty() {
-                        bail!("audio conversion error")
-                    }
-
-                    toast.send(ToastUpdate::caption("extracting thumbnail..."))?;
-                    let cover_bytes = extract_thumbnail(&audio_bytes)?;
-
-                    toast.send(ToastUpdate::caption("loading cover..."))?;
-                    if !cover_bytes.is_empty() {
-                        let image = image::load_from_memory(&cover_bytes)?;
-                        let cover_texture_handle =
-                            load_egui_image(&ctx_clone, &song.title, &image)?;
-                        song.cover_texture_handle = Some(cover_texture_handle);
-                    }
+            if let Err(error) = loaded_song.as_ref() {
+                status.toast.send(failure_update(error))?;
+            }
+            loaded_song
+        }));
+    }
+    pub fn query_playlist(&mut self, ctx: &Context) {
+        let ctx_clone = ctx.clone();
+        let query_url = self.downloader_state.song.source_url.clone();
+        let pipeline = self.settings.pipeline();
+        let max_concurrent_downloads = self.settings.max_concurrent_downloads.max(1);
+        let toast = self.toasts.info("initializing...").create_channel();
+        let report = self.report.clone();
+        let (progress_sender, progress_receiver) = crossbeam_channel::unbounded();
+        let (queue_sender, queue_receiver) = crossbeam_channel::unbounded();
+        self.downloader_state.progress_receiver = Some(progress_receiver);
+        self.downloader_state.queue_receiver = Some(queue_receiver);
+        self.downloader_state.applied_batch_tags = None;
 
-                    toast.send(ToastUpdate::caption("parsing metadata..."))?;
-                    let audio_details = extract_metadata(&audio_bytes)?;
-                    song.update_metadata_from_json(audio_details);
+        let _ = self.stop_current_playing_song();
 
-                    song.cover_bytes = cover_bytes;
-                    song.audio_bytes = converted_audio_bytes;
-                    song.source_url = query_url;
-                } else {
-                    toast.send(ToastUpdate::caption("downloading audio..."))?;
-                    let (audio_bytes, audio_details) = download_audio(&query_url)?;
+        let loading_queue = self.spawn_cancellable("query_playlist", move || {
+            let status = LoadingStatus {
+                toast,
+                progress: progress_sender,
+                prefix: String::new(),
+            };
+            let loaded_queue = (|| {
+                status.caption("reading playlist...")?;
+                let entries = download_playlist(&query_url, &pipeline.ytdl_options)?;
+                let total = entries.len();
+                if total == 0 {
+                    bail!("playlist is empty")
+                }
+                queue_sender.send(QueueUpdate::Entries(
+                    entries
+                        .iter()
+                        .map(|entry| (entry.title.clone(), entry.url.clone()))
+                        .collect(),
+                ))?;
 
-                    if audio_bytes.is_empty() {
-                        bail!("download error")
-                    }
+                *report.lock() = BatchReport::default();
+                report.lock().begin();
+                status.caption(&format!("downloading {total} entries..."))?;
 
-                    toast.send(ToastUpdate::caption("converting audio..."))?;
-                    let converted_audio_bytes = convert_audio(&audio_bytes)?;
+                let jobs = Mutex::new(entries.into_iter().enumerate().collect::<VecDeque<_>>());
+                // the workers answer to the playlist's token
+                let cancel_token = current_cancel_token();
+                let loaded_count = thread::scope(|scope| {
+                    let workers = (0..max_concurrent_downloads.min(total))
+                        .map(|_| {
+                            scope.spawn(|| {
+                                set_cancel_token(cancel_token.clone());
+                                let mut loaded_count = 0;
+                                loop {
+                                    let job = jobs.lock().pop_front();
+                                    let Some((index, entry)) = job else {
+                                        break;
+                                    };
+                                    if check_cancelled().is_err() {
+                                        break;
+                                    }
+                                    let started = Instant::now();
+                                    let _ = queue_sender
+                                        .send(QueueUpdate::Status(index, QueueStatus::Downloading));
+                                    // the toast shows whichever worker reported last,
+                                    // `update_state` replaces the progress with the overall one
+                                    let worker_status = LoadingStatus {
+                                        toast: status.toast.clone(),
+                                        progress: status.progress.clone(),
+                                        prefix: format!("[{}/{total}] ", index + 1),
+                                    };
+                                    let loaded_song =
+                                        pipeline.load_remote(entry.url.clone(), &worker_status);
+                                    report.lock().record(ReportEntry::from_result(
+                                        entry.url,
+                                        &entry.title,
+                                        &loaded_song,
+                                        started,
+                                    ));
+                                    let update = match loaded_song {
+                                        Ok(song) => {
+                                            loaded_count += 1;
+                                            QueueUpdate::Loaded(index, song)
+                                        }
+                                        Err(error) => QueueUpdate::Status(
+                                            index,
+                                            QueueStatus::Failed(error.to_string()),
+                                        ),
+                                    };
+                                    let _ = queue_sender.send(update);
+                                    ctx_clone.request_repaint();
+                                }
+                                loaded_count
+                            })
+                        })
+                        .collect::<Vec<_>>();
+                    // a panicking worker only loses its own entries
+                    workers
+                        .into_iter()
+                        .map(|worker| worker.join().unwrap_or_default())
+                        .sum::<usize>()
+                });
+                check_cancelled()?;
 
-                    if converted_audio_bytes.is_empty() {
-                        bail!("audio conversion error")
-                    }
+                if loaded_count == 0 {
+                    bail!("none of the {total} entries could be downloaded")
+                }
+                status.toast.send(
+                    ToastUpdate::caption(format!("loaded {loaded_count} of {total}"))
+                        .with_level(egui_notify::ToastLevel::Success)
+                        .with_fallback_options(ToastOptions::default()),
+                )?;
+                anyhow::Ok(())
+            })();
 
-                    toast.send(ToastUpdate::caption("downloading thumbnail..."))?;
-                    let image_output = download_thumbnail(&json_read(&audio_details, "thumbnail"))?;
+            if let Err(error) = loaded_queue.as_ref() {
+                status.toast.send(failure_update(error))?;
+            }
+            loaded_queue
+        });
+        self.downloader_state.loading_queue = Some(loading_queue);
+    }
+    /// lets whatever is loading finish, the window closes once it has
+    pub fn wait_then_quit(&mut self) {
+        self.confirming_quit = false;
+        self.quit_when_done = true;
+        self.toasts.info("quitting once loading is done, cancel to stay");
+    }
+    /// cancels whatever is loading, the window closes on the next frame
+    pub fn confirm_quit(&mut self) {
+        self.cancel();
+        self.confirming_quit = false;
+        self.quit_confirmed = true;
+    }
+    /// runs `f` on its own thread with a fresh cancel token
+    fn spawn_cancellable<T: Send + 'static>(
+        &mut self,
+        thread_name: &str,
+        f: impl FnOnce() -> T + Send + 'static,
+    ) -> Promise<T> {
+        let cancel_token = CancelToken::default();
+        self.cancel_token = cancel_token.clone();
+        Promise::spawn_thread(thread_name, move || {
+            set_cancel_token(cancel_token);
+            f()
+        })
+    }
+    pub fn cancel(&mut self) {
+        self.cancel_token.cancel();
+        self.quit_when_done = false;
+        self.downloader_state.loading_song = None;
+        self.downloader_state.loading_queue = None;
+        self.downloader_state.queue_receiver = None;
+        // nothing will update the unfinished entries anymore
+        for entry in &mut self.downloader_state.queue {
+            if matches!(entry.status, QueueStatus::Queued | QueueStatus::Downloading) {
+                entry.status = QueueStatus::Failed(String::from("cancelled"));
+            }
+        }
+    }
+    /// writes `DownloaderState::batch_tags` onto every finished song in the queue
+    pub fn apply_to_queue(&mut self) {
+        let state = &mut self.downloader_state;
+        let batch_tags = state.batch_tags.clone();
+        let mut applied = 0;
+        for (index, entry) in state.queue.iter_mut().enumerate() {
+            if entry.status != QueueStatus::Done {
+                continue;
+            }
+            // the selected entry's song is swapped out into `state.song`
+            if index == state.queue_index {
+                batch_tags.apply(&mut state.song);
+            } else {
+                batch_tags.apply(&mut entry.song);
+            }
+            applied += 1;
+        }
+        state.applied_batch_tags = Some(batch_tags);
+        let total = state.queue.len();
+        self.toasts.success(format!("applied tags to {applied}/{total} songs"));
+    }
+    pub fn select_queue_entry(&mut self, index: usize) {
+        if index == self.downloader_state.queue_index
+            || self
+                .downloader_state
+                .queue
+                .get(index)
+                .map_or(true, |entry| entry.status != QueueStatus::Done)
+        {
+            return;
+        }
+        let _ = self.stop_current_playing_song();
+        let state = &mut self.downloader_state;
+        state.queue[state.queue_index].song = std::mem::take(&mut state.song);
+        state.song = std::mem::take(&mut state.queue[index].song);
+        state.queue_index = index;
+        state.song_origin = Origin::from_link(&state.song.source_url);
+        state.song_handle = None;
+    }
+    /// the closest finished entry in `direction` (1 or -1), following `Settings::queue_end`
+    fn neighboring_queue_entry(&self, direction: isize) -> Option<usize> {
+        let queue = &self.downloader_state.queue;
+        let len = queue.len() as isize;
+        let wrap = self.settings.queue_end == QueueEnd::Wrap;
+        (1..len)
+            .map(|step| self.downloader_state.queue_index as isize + step * direction)
+            .take_while(|index| wrap || (0..len).contains(index))
+            .map(|index| index.rem_euclid(len) as usize)
+            .find(|&index| queue[index].status == QueueStatus::Done)
+    }
+    fn step_song(&mut self, direction: isize) {
+        let Some(index) = self.neighboring_queue_entry(direction) else {
+            return;
+        };
+        self.select_queue_entry(index);
+        if self.settings.autoplay_queue {
+            if let Err(error) = self.toggle_song_playback() {
+                self.toasts.error(format!("failed playing song: {error}"));
+            }
+        }
+    }
+    pub fn next_song(&mut self) {
+        self.step_song(1);
+    }
+    pub fn prev_song(&mut self) {
+        self.step_song(-1);
+    }
+}
 
-                    toast.send(ToastUpdate::caption("parsing metadata..."))?;
-                    song.update_metadata_from_json(audio_details);
+/// how close to its end a stopped song has to be to count as played through
+const AUTO_ADVANCE_TOLERANCE_SECS: f64 = 0.25;
 
-                    let mut cover_bytes = vec![];
+/// where the loading steps report to, captions go to the toast and progress to the downloader
+struct LoadingStatus {
+    toast: Sender<ToastUpdate>,
+    progress: Sender<Option<f32>>,
+    prefix: String,
+}
 
-                    toast.send(ToastUpdate::caption("loading cover..."))?;
-                    if !image_output.stdout.is_empty() {
-                        let image = image::load_from_memory(&image_output.stdout)?;
-                        let cover_texture_handle =
-                            load_egui_image(&ctx_clone, &song.title, &image)?;
-                        image.write_to(
-                            &mut Cursor::new(&mut cover_bytes),
-                            image::ImageFormat::Jpeg,
-                        )?;
-                        song.cover_texture_handle = Some(cover_texture_handle);
-                    }
+impl Status for LoadingStatus {
+    fn caption(&self, caption: &str) -> Result<()> {
+        check_cancelled()?;
+        self.toast
+            .send(ToastUpdate::caption(format!("{}{caption}", self.prefix)))?;
+        Ok(())
+    }
+    fn progress(&self, progress: Option<f32>) {
+        let _ = self.progress.send(progress);
+    }
+}
 
-                    song.cover_bytes = cover_bytes;
-                    song.audio_bytes = converted_audio_bytes;
-                    song.source_url = query_url;
-                }
+/// a bare toast for steps that don't report progress
+impl Status for Sender<ToastUpdate> {
+    fn caption(&self, caption: &str) -> Result<()> {
+        check_cancelled()?;
+        self.send(ToastUpdate::caption(caption))?;
+        Ok(())
+    }
+    fn progress(&self, _progress: Option<f32>) {}
+}
 
-                toast.send(ToastUpdate::caption("reading song..."))?;
-                song.update_audio_frames()?;
-                song.update_current_volume()?;
+fn failure_update(error: &anyhow::Error) -> ToastUpdate {
+    if error.is::<Cancelled>() {
+        ToastUpdate::caption("cancelled").with_fallback_options(ToastOptions::default())
+    } else {
+        ToastUpdate::caption(format!("failed: {error}"))
+            .with_fallback_options(ToastOptions::default())
+            .with_level(egui_notify::ToastLevel::Error)
+    }
+}
 
-                anyhow::Ok(())
-            })() {
-                toast.send(
-                    ToastUpdate::caption(format!("failed: {error}"))
-                        .with_fallback_options(ToastOptions::default())
-                        .with_level(egui_notify::ToastLevel::Error),
-                )?;
-                return Err(error);
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            Ok(song)
-        }));
+    #[test]
+    fn seek_position_is_proportional() {
+        let duration = Duration::from_secs(10);
+        assert!((seek_position(duration, 0.5) - 5.).abs() < 1e-6);
+        assert_eq!(seek_position(duration, 0.), 0.);
+        assert_eq!(seek_position(duration, 1.5), 10.);
     }
 }
@@ -0,0 +1,116 @@
+use std::{
+    cell::Cell,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    command::AudioFormat,
+    pipeline::{SongPipeline, Status},
+};
+
+const USAGE: &str = "usage: songdl <url> --out <dir> [--format <mp3|flac|opus|m4a|wav>] \
+                     [--volume-offset <db>]";
+
+pub struct CliArgs {
+    pub url: String,
+    pub out: PathBuf,
+    pub format: Option<AudioFormat>,
+    pub volume_offset: Option<f32>,
+}
+
+impl CliArgs {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut url = None;
+        let mut out = None;
+        let mut format = None;
+        let mut volume_offset = None;
+
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let mut value =
+                |flag: &str| args.next().with_context(|| format!("{flag} needs a value"));
+            match arg.as_str() {
+                "--out" | "-o" => out = Some(PathBuf::from(value("--out")?)),
+                "--format" | "-f" => {
+                    let name = value("--format")?.to_lowercase();
+                    format = Some(
+                        AudioFormat::ALL
+                            .into_iter()
+                            .find(|format| format.extension() == format!(".{name}"))
+                            .with_context(|| format!("unknown format \"{name}\""))?,
+                    );
+                }
+                "--volume-offset" => {
+                    let offset = value("--volume-offset")?;
+                    volume_offset = Some(
+                        offset
+                            .parse::<f32>()
+                            .with_context(|| format!("invalid volume offset \"{offset}\""))?,
+                    );
+                }
+                "--help" | "-h" => bail!(USAGE),
+                _ if arg.starts_with('-') => bail!("unknown option \"{arg}\"\n{USAGE}"),
+                _ if url.is_none() => url = Some(arg),
+                _ => bail!("only one url can be given\n{USAGE}"),
+            }
+        }
+
+        Ok(Self {
+            url: url.with_context(|| format!("no url given\n{USAGE}"))?,
+            out: out.with_context(|| format!("no output directory given\n{USAGE}"))?,
+            format,
+            volume_offset,
+        })
+    }
+}
+
+/// prints captions as lines and progress as whole percentages
+#[derive(Default)]
+struct StdoutStatus {
+    last_percent: Cell<Option<u32>>,
+}
+
+impl Status for StdoutStatus {
+    fn caption(&self, caption: &str) -> Result<()> {
+        if self.last_percent.take().is_some() {
+            println!();
+        }
+        println!("{caption}");
+        Ok(())
+    }
+    fn progress(&self, progress: Option<f32>) {
+        let Some(progress) = progress else {
+            return;
+        };
+        let percent = (progress * 100.).round() as u32;
+        if self.last_percent.get() != Some(percent) {
+            self.last_percent.set(Some(percent));
+            print!("\r{percent}%");
+            let _ = io::stdout().flush();
+        }
+    }
+}
+
+/// downloads, converts and saves `args.url` through `pipeline`, without the gui
+pub fn run(args: CliArgs, mut pipeline: SongPipeline) -> Result<()> {
+    // there's no waveform to show
+    pipeline.cache_waveform_peaks = false;
+    if let Some(format) = args.format {
+        pipeline.encode_options.format = format;
+    }
+    let status = StdoutStatus::default();
+
+    let mut song = pipeline.load(args.url, &status)?;
+
+    if let Some(volume_offset) = args.volume_offset {
+        status.caption("setting volume...")?;
+        song.apply_volume_offset(volume_offset)?;
+    }
+
+    let saved_path = pipeline.save(&mut song, &args.out, &status)?;
+    status.caption(&format!("saved to {}", saved_path.display()))?;
+    Ok(())
+}
@@ -1,21 +1,48 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 
 use parking_lot::Mutex;
 use regex::Regex;
-use serde_json::{Map, Value};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
 use std::{
+    cell::RefCell,
     collections::HashMap,
-    io::Read,
-    os::windows::process::CommandExt,
-    process::{Command, Output},
-    sync::OnceLock,
+    fmt::Display,
+    io::{BufRead, BufReader, Read, Write},
+    path::Path,
+    process::{Child, Command, Output, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+    },
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
 };
 
-use crate::app::tempfile;
+use tempfile::NamedTempFile;
 
 pub const DEFAULT_YT_DL_COMMAND: &str = "yt-dlp";
 pub const DEFAULT_FFMPEG_COMMAND: &str = "ffmpeg";
-pub const DEFAULT_CURL_COMMAND: &str = "curl";
+
+pub fn json_read(json: &Value, field: &str) -> String {
+    json.get(field)
+        .unwrap_or(&json!(""))
+        .to_string()
+        .replace("\"", "")
+}
+
+pub fn tempfile(contents: &[u8]) -> Result<(NamedTempFile, String)> {
+    tempfile_with_suffix(contents, "")
+}
+
+/// `contents` is fully written and flushed, so other processes can read it by path
+pub fn tempfile_with_suffix(contents: &[u8], suffix: &str) -> Result<(NamedTempFile, String)> {
+    let mut tempfile = tempfile::Builder::new().suffix(suffix).tempfile()?;
+    let path = tempfile.path().to_string_lossy().to_string();
+    tempfile.write_all(contents)?;
+    tempfile.flush()?;
+    Ok((tempfile, path))
+}
 
 type CommandHashMap = Mutex<HashMap<&'static str, String>>;
 
@@ -24,10 +51,535 @@ fn command_map() -> &'static CommandHashMap {
     MAP.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
+/// every child with the token of the op that started it
+type ChildHashMap = Mutex<HashMap<u32, (Arc<Mutex<Child>>, CancelToken)>>;
+
+fn running_children() -> &'static ChildHashMap {
+    static MAP: OnceLock<ChildHashMap> = OnceLock::new();
+    MAP.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+const CHILD_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+pub trait TrackedCommand {
+    /// like `Command::output`, but the child is registered so `kill_running_commands` can reach it
+    fn tracked_output(&mut self) -> Result<Output>;
+    /// same as `tracked_output`, but every stderr line is handed to `on_stderr_line` as it arrives
+    fn tracked_output_with_stderr(&mut self, on_stderr_line: impl FnMut(&str)) -> Result<Output>;
+    /// same as `tracked_output`, but `stdin` is written to the child's stdin
+    fn tracked_output_with_stdin(&mut self, stdin: &[u8]) -> Result<Output>;
+    /// same as `tracked_output`, but a failed exit is an error with the end of stderr
+    fn checked_output(&mut self) -> Result<Output>;
+}
+
+impl TrackedCommand for Command {
+    fn tracked_output(&mut self) -> Result<Output> {
+        self.tracked_output_with_stderr(|_| ())
+    }
+    fn tracked_output_with_stderr(&mut self, on_stderr_line: impl FnMut(&str)) -> Result<Output> {
+        run_tracked(self, None, on_stderr_line)
+    }
+    fn tracked_output_with_stdin(&mut self, stdin: &[u8]) -> Result<Output> {
+        run_tracked(self, Some(stdin), |_| ())
+    }
+    fn checked_output(&mut self) -> Result<Output> {
+        let output = self.tracked_output()?;
+        check_status(self, output)
+    }
+}
+
+fn run_tracked(
+    command: &mut Command,
+    stdin: Option<&[u8]>,
+    mut on_stderr_line: impl FnMut(&str),
+) -> Result<Output> {
+    let mut child = configure_command(command)
+        .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdin_pipe = child.stdin.take();
+    let stdout_reader = spawn_pipe_reader(child.stdout.take());
+    let stderr_pipe = child.stderr.take();
+
+    let child_id = child.id();
+    let child = Arc::new(Mutex::new(child));
+    let cancel_token = current_cancel_token();
+    running_children()
+        .lock()
+        .insert(child_id, (child.clone(), cancel_token.clone()));
+    if cancel_token.is_cancelled() {
+        let _ = child.lock().kill();
+    }
+
+    thread::scope(|scope| -> Result<Output> {
+        if let (Some(stdin), Some(mut stdin_pipe)) = (stdin, stdin_pipe) {
+            scope.spawn(move || {
+                // ffmpeg stops reading early when it only needs the header, so this can fail,
+                // dropping the pipe afterwards sends eof
+                let _ = stdin_pipe.write_all(stdin);
+            });
+        }
+
+        // the child is registered, so a cancel kills it and closes the pipe
+        let mut stderr = vec![];
+        if let Some(stderr_pipe) = stderr_pipe {
+            let mut stderr_pipe = BufReader::new(stderr_pipe);
+            let mut line = vec![];
+            while let Ok(read) = stderr_pipe.read_until(b'\n', &mut line) {
+                if read == 0 {
+                    break;
+                }
+                on_stderr_line(String::from_utf8_lossy(&line).trim_end());
+                stderr.append(&mut line);
+            }
+        }
+
+        let status = loop {
+            if cancel_token.is_cancelled() {
+                let _ = child.lock().kill();
+            }
+            match child.lock().try_wait() {
+                Ok(Some(status)) => break Ok(status),
+                Ok(None) => (),
+                Err(error) => break Err(error),
+            }
+            thread::sleep(CHILD_POLL_INTERVAL);
+        };
+        running_children().lock().remove(&child_id);
+        check_cancelled()?;
+
+        Ok(Output {
+            status: status?,
+            stdout: stdout_reader.join().unwrap_or_default(),
+            stderr,
+        })
+    })
+}
+
+fn spawn_pipe_reader(pipe: Option<impl Read + Send + 'static>) -> JoinHandle<Vec<u8>> {
+    thread::spawn(move || {
+        let mut bytes = vec![];
+        if let Some(mut pipe) = pipe {
+            let _ = pipe.read_to_end(&mut bytes);
+        }
+        bytes
+    })
+}
+
+/// kills every yt-dl/ffmpeg child still running so nothing is orphaned
+pub fn kill_running_commands() {
+    for (_, (child, _)) in running_children().lock().drain() {
+        let _ = child.lock().kill();
+    }
+}
+
+/// cancels one background op, its thread picks the token up with `set_cancel_token`
+#[derive(Default, Clone)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    /// kills the children the op started and makes any later stage of it bail
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+        for (child, token) in running_children().lock().values() {
+            if Arc::ptr_eq(&token.0, &self.0) {
+                let _ = child.lock().kill();
+            }
+        }
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+thread_local! {
+    /// threads that never set one get a token nothing can cancel
+    static CANCEL_TOKEN: RefCell<CancelToken> = RefCell::default();
+}
+
+/// makes `check_cancelled` and the tracked commands on this thread answer to `token`
+pub fn set_cancel_token(token: CancelToken) {
+    CANCEL_TOKEN.with(|current| *current.borrow_mut() = token);
+}
+
+pub fn current_cancel_token() -> CancelToken {
+    CANCEL_TOKEN.with(|current| current.borrow().clone())
+}
+
+#[derive(Debug)]
+pub struct Cancelled;
+
+impl Display for Cancelled {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+pub fn check_cancelled() -> Result<()> {
+    if current_cancel_token().is_cancelled() {
+        bail!(Cancelled)
+    }
+    Ok(())
+}
+
+/// like `thread::sleep`, but wakes up early with `Cancelled`
+pub fn sleep_cancellable(duration: Duration) -> Result<()> {
+    let started = Instant::now();
+    while started.elapsed() < duration {
+        check_cancelled()?;
+        thread::sleep(CHILD_POLL_INTERVAL);
+    }
+    check_cancelled()
+}
+
+/// whether `error` looks like a network hiccup that a second attempt could get past
+pub fn is_transient_error(error: &anyhow::Error) -> bool {
+    if error.is::<Cancelled>() {
+        return false;
+    }
+    if let Some(http_error) = error.downcast_ref::<ureq::Error>() {
+        return match http_error {
+            ureq::Error::Status(code, _) => matches!(code, 403 | 408 | 429 | 500..=599),
+            // a bad url or scheme fails the same way every time
+            ureq::Error::Transport(transport) => matches!(
+                transport.kind(),
+                ureq::ErrorKind::Dns
+                    | ureq::ErrorKind::ConnectionFailed
+                    | ureq::ErrorKind::Io
+                    | ureq::ErrorKind::ProxyConnect
+            ),
+        };
+    }
+    let message = error.to_string().to_ascii_lowercase();
+    let matches = |patterns: &[&str]| patterns.iter().any(|pattern| message.contains(pattern));
+    // yt-dl words a 503 as "service unavailable", so these go before the permanent ones
+    let network = [
+        "http error 403",
+        "http error 408",
+        "http error 429",
+        "http error 5",
+        "timed out",
+        "connection",
+        "network",
+        "temporary failure",
+    ];
+    if matches(&network) {
+        return true;
+    }
+    let permanent = [
+        "video unavailable",
+        "this video is unavailable",
+        "requested format is not available",
+        "private video",
+        "unsupported url",
+        "has been removed",
+        "copyright",
+    ];
+    !matches(&permanent) && matches(&["unable to download webpage", "incomplete"])
+}
+
+#[cfg(windows)]
 pub const WIN_FLAG_CREATE_NO_WINDOW: u32 = 0x08000000;
 
-pub const FFMPEG_AUDIO_FORMAT: &str = "mp3";
-pub const FFMPEG_AUDIO_FORMAT_EXT: &str = ".mp3";
+/// applies platform specific process flags, currently only hides the console window on windows
+pub fn configure_command(command: &mut Command) -> &mut Command {
+    #[cfg(windows)]
+    {
+        use std::os::windows::process::CommandExt;
+        command.creation_flags(WIN_FLAG_CREATE_NO_WINDOW);
+    }
+    command
+}
+
+// sites without a separate audio-only stream fall back to the best combined stream,
+// `convert_audio` drops the video track with `-vn` so the result is the same
+pub const YT_DL_FORMAT_SELECTOR: &str = "bestaudio/best";
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum AudioFormat {
+    #[default]
+    Mp3,
+    Flac,
+    Opus,
+    M4a,
+    Wav,
+}
+
+impl AudioFormat {
+    pub const ALL: [AudioFormat; 5] = [Self::Mp3, Self::Flac, Self::Opus, Self::M4a, Self::Wav];
+
+    pub fn muxer(&self) -> &'static str {
+        match self {
+            Self::Mp3 => "mp3",
+            Self::Flac => "flac",
+            Self::Opus => "opus",
+            Self::M4a => "ipod",
+            Self::Wav => "wav",
+        }
+    }
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Mp3 => ".mp3",
+            Self::Flac => ".flac",
+            Self::Opus => ".opus",
+            Self::M4a => ".m4a",
+            Self::Wav => ".wav",
+        }
+    }
+    /// ogg and wav have no attached picture stream, so covers are skipped for them
+    pub fn supports_cover(&self) -> bool {
+        matches!(self, Self::Mp3 | Self::Flac | Self::M4a)
+    }
+    pub fn supports_sample_format(&self) -> bool {
+        matches!(self, Self::Flac | Self::Wav)
+    }
+    pub fn is_lossy(&self) -> bool {
+        matches!(self, Self::Mp3 | Self::Opus | Self::M4a)
+    }
+    /// the container a stream of `codec` can be copied into as is
+    pub fn from_codec(codec: &str) -> Option<Self> {
+        match codec {
+            "mp3" => Some(Self::Mp3),
+            "flac" => Some(Self::Flac),
+            "opus" => Some(Self::Opus),
+            "aac" | "alac" => Some(Self::M4a),
+            "pcm_u8" => Some(Self::Wav),
+            _ if codec.starts_with("pcm_") && codec.ends_with("le") => Some(Self::Wav),
+            _ => None,
+        }
+    }
+    /// lossless codecs that have no container here to be copied into, like aiff's big endian pcm
+    fn is_lossless_codec(codec: &str) -> bool {
+        codec.starts_with("pcm_")
+            || ["flac", "alac", "wavpack", "ape", "tta", "tak", "mlp", "truehd"].contains(&codec)
+    }
+    /// the mp4 muxer seeks back to write its index, so it can't stream to stdout
+    fn requires_seekable_output(&self) -> bool {
+        matches!(self, Self::M4a)
+    }
+}
+
+impl Display for AudioFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", &self.extension()[1..])
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum SampleFormat {
+    #[default]
+    Source,
+    S16,
+    S24,
+    S32,
+}
+
+impl SampleFormat {
+    pub const ALL: [SampleFormat; 4] = [Self::Source, Self::S16, Self::S24, Self::S32];
+
+    /// ffmpeg args for encoding `audio_format` at this bit depth, empty where it doesn't apply
+    pub fn args(&self, audio_format: AudioFormat) -> Vec<String> {
+        let args: &[&str] = match (audio_format, self) {
+            (_, Self::Source) => &[],
+            (AudioFormat::Wav, Self::S16) => &["-c:a", "pcm_s16le"],
+            (AudioFormat::Wav, Self::S24) => &["-c:a", "pcm_s24le"],
+            (AudioFormat::Wav, Self::S32) => &["-c:a", "pcm_s32le"],
+            (AudioFormat::Flac, Self::S16) => &["-sample_fmt", "s16"],
+            (AudioFormat::Flac, Self::S24) => &["-sample_fmt", "s32", "-bits_per_raw_sample", "24"],
+            (AudioFormat::Flac, Self::S32) => &["-sample_fmt", "s32", "-strict", "experimental"],
+            _ => &[],
+        };
+        args.iter().map(|a| a.to_string()).collect()
+    }
+}
+
+impl Display for SampleFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Source => write!(f, "preserve source"),
+            Self::S16 => write!(f, "16-bit"),
+            Self::S24 => write!(f, "24-bit"),
+            Self::S32 => write!(f, "32-bit"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum Bitrate {
+    #[default]
+    EncoderDefault,
+    /// constant bitrate in kbps
+    Cbr(u32),
+    /// lame's vbr quality, 0 is best and 9 is smallest
+    Vbr(u8),
+}
+
+impl Bitrate {
+    pub const ALL: [Bitrate; 9] = [
+        Self::EncoderDefault,
+        Self::Cbr(96),
+        Self::Cbr(128),
+        Self::Cbr(192),
+        Self::Cbr(256),
+        Self::Cbr(320),
+        Self::Vbr(0),
+        Self::Vbr(2),
+        Self::Vbr(5),
+    ];
+
+    /// ffmpeg args for encoding `audio_format` at this bitrate, empty for lossless formats.
+    /// only mp3 has a vbr quality scale, the other encoders keep their default for it
+    pub fn args(&self, audio_format: AudioFormat) -> Vec<String> {
+        if !audio_format.is_lossy() {
+            return vec![];
+        }
+        match (audio_format, self) {
+            (_, Self::EncoderDefault) => vec![],
+            (AudioFormat::Opus, Self::Cbr(kbps)) => {
+                vec!["-b:a".into(), format!("{kbps}k"), "-vbr".into(), "off".into()]
+            }
+            (_, Self::Cbr(kbps)) => vec!["-b:a".into(), format!("{kbps}k")],
+            (AudioFormat::Mp3, Self::Vbr(quality)) => vec!["-q:a".into(), quality.to_string()],
+            (_, Self::Vbr(_)) => vec![],
+        }
+    }
+}
+
+impl Display for Bitrate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::EncoderDefault => write!(f, "encoder default"),
+            Self::Cbr(kbps) => write!(f, "{kbps} kbps"),
+            Self::Vbr(quality) => write!(f, "vbr V{quality}"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub struct EncodeOptions {
+    pub format: AudioFormat,
+    pub sample_format: SampleFormat,
+    pub bitrate: Bitrate,
+    /// copy the source stream instead of re-encoding it, when there's a container for it
+    pub keep_original: bool,
+}
+
+impl EncodeOptions {
+    fn args(&self) -> Vec<String> {
+        let mut args = self.sample_format.args(self.format);
+        args.extend(self.bitrate.args(self.format));
+        args
+    }
+}
+
+pub const COOKIE_BROWSERS: [&str; 8] =
+    ["brave", "chrome", "chromium", "edge", "firefox", "opera", "safari", "vivaldi"];
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq)]
+pub struct YtdlOptions {
+    pub cookies_file: Option<String>,
+    pub cookies_from_browser: Option<String>,
+    pub proxy_url: Option<String>,
+    /// passed to `-f`, falls back to `YT_DL_FORMAT_SELECTOR` when empty
+    pub format: String,
+    /// passed to `--limit-rate`, in bytes per second with an optional K or M suffix
+    pub rate_limit: Option<String>,
+    /// how many times a transient download failure is tried before it's surfaced
+    pub max_attempts: u32,
+}
+
+impl YtdlOptions {
+    fn format(&self) -> &str {
+        match self.format.trim() {
+            "" => YT_DL_FORMAT_SELECTOR,
+            format => format,
+        }
+    }
+    fn args(&self) -> Result<Vec<String>> {
+        let mut args = vec![];
+        if let Some(rate_limit) = self.rate_limit.as_ref() {
+            if !is_valid_rate_limit(rate_limit) {
+                bail!("invalid download rate limit \"{rate_limit}\", expected e.g. 500K or 2M")
+            }
+            args.extend(["--limit-rate".to_string(), rate_limit.clone()]);
+        }
+        if let Some(proxy_url) = self.proxy_url.as_ref() {
+            args.extend(["--proxy".to_string(), proxy_url.clone()]);
+        }
+        if let Some(cookies_file) = self.cookies_file.as_ref() {
+            args.extend(["--cookies".to_string(), cookies_file.clone()]);
+        }
+        if let Some(browser) = self.cookies_from_browser.as_ref() {
+            args.extend(["--cookies-from-browser".to_string(), browser.clone()]);
+        }
+        Ok(args)
+    }
+}
+
+/// a number, optionally followed by K or M
+pub fn is_valid_rate_limit(rate_limit: &str) -> bool {
+    let number = rate_limit.strip_suffix(['K', 'k', 'M', 'm']).unwrap_or(rate_limit);
+    number.parse::<f32>().map_or(false, |number| number.is_finite() && number > 0.)
+        && number.chars().all(|c| c.is_ascii_digit() || c == '.')
+}
+
+/// yt-dl prints its failures as `ERROR: ...` lines
+fn ytdl_error_line(line: &str) -> Option<String> {
+    line.strip_prefix("ERROR:").map(|error| error.trim().to_string())
+}
+
+fn ytdl_error(stderr: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(stderr).lines().filter_map(ytdl_error_line).last()
+}
+
+/// how many of the last stderr lines end up in an error
+const STDERR_TAIL_LINES: usize = 4;
+
+/// the last few lines of `stderr` that pass `is_relevant`, for when there's no better message
+fn stderr_tail(stderr: &[u8], is_relevant: impl Fn(&str) -> bool) -> String {
+    let stderr = String::from_utf8_lossy(stderr);
+    let lines = stderr
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && is_relevant(line))
+        .collect::<Vec<_>>();
+    lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..].join("\n")
+}
+
+/// the `ERROR:` line if yt-dl printed one, otherwise the exit status and the end of its output,
+/// leaving out the details json and progress lines which share stderr with the errors
+fn ytdl_failure(output: &Output) -> anyhow::Error {
+    ytdl_error(&output.stderr).map(anyhow::Error::msg).unwrap_or_else(|| {
+        let tail = stderr_tail(&output.stderr, |line| {
+            !line.starts_with('{') && !line.starts_with("[download]")
+        });
+        anyhow!("yt-dl exited with {}: {tail}", output.status)
+    })
+}
+
+/// the command's name, its exit status and the end of its log
+fn command_failure(command: &Command, output: &Output) -> anyhow::Error {
+    let program = Path::new(command.get_program());
+    let name = program.file_stem().unwrap_or(program.as_os_str()).to_string_lossy();
+    anyhow!(
+        "{name} exited with {}: {}",
+        output.status,
+        stderr_tail(&output.stderr, |_| true)
+    )
+}
+
+/// passes `output` through if `command` exited successfully
+fn check_status(command: &Command, output: Output) -> Result<Output> {
+    if !output.status.success() {
+        return Err(command_failure(command, &output));
+    }
+    Ok(output)
+}
+
+pub const PCM_PREVIEW_SAMPLE_RATE: u32 = 44100;
 
 pub fn get_command(name: &str) -> String {
     command_map()
@@ -45,94 +597,366 @@ pub fn set_command(name: &'static str, value: Option<String>) {
     };
 }
 
-pub fn download_audio(query_url: &String) -> Result<(Vec<u8>, Value)> {
+/// `on_progress` is called with the downloaded ratio, from 0 to 1
+pub fn download_audio(
+    query_url: &String,
+    ytdl_options: &YtdlOptions,
+    mut on_progress: impl FnMut(f32),
+) -> Result<(Vec<u8>, Value)> {
+    let progress_regex = Regex::new(r"^\[download\]\s+([\d.]+)%")?;
+    let mut details = None;
+    // with `-o -` both the details json and the progress lines end up on stderr,
+    // so only the exit status tells whether it failed
     let output = Command::new(get_command(DEFAULT_YT_DL_COMMAND))
         .args([
             "-j",
             "-f",
-            "bestaudio",
+            ytdl_options.format(),
             "--no-playlist",
             "--no-simulate",
             "--ignore-config",
             "--no-warnings",
+            "--newline",
+            "--progress",
             "-o",
             "-",
-            &query_url,
         ])
-        .creation_flags(WIN_FLAG_CREATE_NO_WINDOW)
-        .output()?;
+        .args(ytdl_options.args()?)
+        .arg(&query_url)
+        .tracked_output_with_stderr(|line| {
+            if line.starts_with('{') {
+                details = Some(serde_json::from_str::<Value>(line));
+            } else if let Some(percent) = progress_regex
+                .captures(line)
+                .and_then(|captures| captures[1].parse::<f32>().ok())
+            {
+                on_progress((percent / 100.).clamp(0., 1.));
+            }
+        })?;
 
-    Ok((output.stdout, serde_json::from_slice(&output.stderr)?))
+    if !output.status.success() {
+        return Err(ytdl_failure(&output));
+    }
+    if output.stdout.is_empty() {
+        bail!(
+            "yt-dl didn't output any audio: {}",
+            stderr_tail(&output.stderr, |line| !line.starts_with('{'))
+        )
+    }
+
+    Ok((output.stdout, details.context("no details from yt-dl")??))
 }
 
-pub fn convert_audio(audio_bytes: &[u8]) -> Result<Vec<u8>> {
-    let (_audio_tfile, audio_tfilepath) = tempfile(audio_bytes)?;
-    Ok(Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
-        .args([
+pub struct PlaylistEntry {
+    pub url: String,
+    pub title: String,
+}
+
+pub fn download_playlist(
+    query_url: &str,
+    ytdl_options: &YtdlOptions,
+) -> Result<Vec<PlaylistEntry>> {
+    let output = Command::new(get_command(DEFAULT_YT_DL_COMMAND))
+        .args(["-J", "--flat-playlist", "--ignore-config", "--no-warnings"])
+        .args(ytdl_options.args()?)
+        .arg(query_url)
+        .tracked_output()?;
+
+    if !output.status.success() {
+        return Err(ytdl_failure(&output));
+    }
+
+    let playlist: Value = serde_json::from_slice(&output.stdout)?;
+    Ok(playlist
+        .get("entries")
+        .and_then(|entries| entries.as_array())
+        .context("not a playlist")?
+        .iter()
+        .filter_map(|entry| {
+            let url = ["url", "webpage_url"]
+                .into_iter()
+                .map(|field| json_read(entry, field))
+                .find(|url| !url.is_empty())?;
+            Some(PlaylistEntry {
+                title: json_read(entry, "title"),
+                url,
+            })
+        })
+        .collect())
+}
+
+/// a single ffmpeg input, piped through stdin unless ffmpeg needs to seek in it
+struct AudioInput<'a> {
+    bytes: &'a [u8],
+    tempfile: Option<(NamedTempFile, String)>,
+}
+
+impl<'a> AudioInput<'a> {
+    fn new(bytes: &'a [u8]) -> Result<Self> {
+        // mp4 based containers usually keep their index at the end
+        let is_mp4 = bytes.get(4..8) == Some(b"ftyp");
+        if is_mp4 {
+            Self::seekable(bytes)
+        } else {
+            Ok(Self { bytes, tempfile: None })
+        }
+    }
+    /// always goes through a temp file, for reading things like the duration from the container
+    fn seekable(bytes: &'a [u8]) -> Result<Self> {
+        Ok(Self {
+            bytes,
+            tempfile: Some(tempfile(bytes)?),
+        })
+    }
+    fn path(&self) -> &str {
+        match &self.tempfile {
+            Some((_, path)) => path,
+            None => "pipe:0",
+        }
+    }
+    fn output(&self, command: &mut Command) -> Result<Output> {
+        match self.tempfile {
+            Some(_) => command.tracked_output(),
+            None => command.tracked_output_with_stdin(self.bytes),
+        }
+    }
+    /// same as `output`, but a failed exit is an error
+    fn checked_output(&self, command: &mut Command) -> Result<Output> {
+        let output = self.output(command)?;
+        check_status(command, output)
+    }
+}
+
+/// appends the muxer and output, going through a temp file for formats that can't stream
+fn encode_output(
+    command: &mut Command,
+    input: &AudioInput,
+    format: AudioFormat,
+) -> Result<Vec<u8>> {
+    command.args(["-f", format.muxer()]);
+    let (output, encoded_bytes) = if format.requires_seekable_output() {
+        let (_output_tfile, output_tfilepath) = tempfile_with_suffix(&[], format.extension())?;
+        let output = input.checked_output(command.args(["-y", &output_tfilepath]))?;
+        (output, std::fs::read(&output_tfilepath)?)
+    } else {
+        let mut output = input.checked_output(command.arg("-"))?;
+        let stdout = std::mem::take(&mut output.stdout);
+        (output, stdout)
+    };
+    if encoded_bytes.is_empty() {
+        return Err(command_failure(command, &output));
+    }
+    Ok(encoded_bytes)
+}
+
+/// returns the converted audio along with the options it ended up encoded with,
+/// which differ from `encode_options` when the original stream was kept
+pub fn convert_audio(
+    audio_bytes: &[u8],
+    encode_options: &EncodeOptions,
+) -> Result<(Vec<u8>, EncodeOptions)> {
+    let input = AudioInput::new(audio_bytes)?;
+    if encode_options.keep_original {
+        let codec = probe_audio_codec(&input)?;
+        if let Some(format) = AudioFormat::from_codec(&codec) {
+            let remuxed_audio = encode_output(
+                Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
+                    .args(["-i", input.path(), "-vn", "-c:a", "copy"]),
+                &input,
+                format,
+            )?;
+            return Ok((remuxed_audio, EncodeOptions { format, ..*encode_options }));
+        }
+        // re-encoding these to a lossy format would throw away what keeping them is for
+        if AudioFormat::is_lossless_codec(&codec) {
+            let flac_options = EncodeOptions {
+                format: AudioFormat::Flac,
+                ..*encode_options
+            };
+            let flac_audio = encode_output(
+                Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
+                    .args(["-i", input.path(), "-vn"])
+                    .args(flac_options.args()),
+                &input,
+                AudioFormat::Flac,
+            )?;
+            return Ok((flac_audio, flac_options));
+        }
+    }
+    let converted_audio = encode_output(
+        Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
+            .args(["-i", input.path(), "-vn"])
+            .args(encode_options.args()),
+        &input,
+        encode_options.format,
+    )?;
+    Ok((converted_audio, *encode_options))
+}
+
+/// the input info ffmpeg prints for `input`
+fn probe_input(input: &AudioInput) -> Result<String> {
+    // without an output ffmpeg only prints the input info, and exits with an error
+    let mut command = Command::new(get_command(DEFAULT_FFMPEG_COMMAND));
+    let output = input.output(command.args(["-hide_banner", "-i", input.path()]))?;
+    Ok(String::from_utf8_lossy(&output.stderr).to_string())
+}
+
+/// the codec of the first audio stream, as ffmpeg names it
+fn probe_audio_codec(input: &AudioInput) -> Result<String> {
+    let codec_re = Regex::new(r"Stream #\S+.*?: Audio: ([0-9a-z_]+)")?;
+    let stderr = probe_input(input)?;
+    Ok(codec_re
+        .captures(&stderr)
+        .context("no audio stream found")?[1]
+        .to_string())
+}
+
+#[derive(Default, Clone, Copy)]
+pub struct AudioInfo {
+    pub duration: Option<f32>,
+    pub bitrate_kbps: Option<u32>,
+    pub sample_rate: Option<u32>,
+}
+
+pub fn probe_audio_info(audio_bytes: &[u8]) -> Result<AudioInfo> {
+    // piped inputs have no length, so ffmpeg can't report their duration
+    let stderr = probe_input(&AudioInput::seekable(audio_bytes)?)?;
+    let duration_re = Regex::new(r"Duration: (\d+):(\d+):(\d+(?:\.\d+)?)")?;
+    // the overall bitrate, streams in some containers don't report their own
+    let bitrate_re = Regex::new(r"Duration: .*?bitrate: (\d+) kb/s")?;
+    let sample_rate_re = Regex::new(r"Stream #\S+.*?: Audio: .*?(\d+) Hz")?;
+
+    let duration = duration_re.captures(&stderr).and_then(|captures| {
+        let hours = captures[1].parse::<f32>().ok()?;
+        let minutes = captures[2].parse::<f32>().ok()?;
+        let seconds = captures[3].parse::<f32>().ok()?;
+        Some(hours * 3600. + minutes * 60. + seconds)
+    });
+    let capture_u32 = |re: &Regex| {
+        re.captures(&stderr)
+            .and_then(|captures| captures[1].parse::<u32>().ok())
+    };
+    Ok(AudioInfo {
+        duration,
+        bitrate_kbps: capture_u32(&bitrate_re),
+        sample_rate: capture_u32(&sample_rate_re),
+    })
+}
+
+/// decodes to interleaved stereo f32 at `PCM_PREVIEW_SAMPLE_RATE`, for containers kira can't read
+pub fn decode_to_pcm(audio_bytes: &[u8]) -> Result<Vec<f32>> {
+    let input = AudioInput::new(audio_bytes)?;
+    let pcm_bytes = input
+        .checked_output(Command::new(get_command(DEFAULT_FFMPEG_COMMAND)).args([
             "-i",
-            &audio_tfilepath,
+            input.path(),
             "-vn",
             "-f",
-            FFMPEG_AUDIO_FORMAT,
+            "f32le",
+            "-acodec",
+            "pcm_f32le",
+            "-ac",
+            "2",
+            "-ar",
+            &PCM_PREVIEW_SAMPLE_RATE.to_string(),
             "-",
-        ])
-        .creation_flags(WIN_FLAG_CREATE_NO_WINDOW)
-        .output()?
-        .stdout)
+        ]))?
+        .stdout;
+    if pcm_bytes.is_empty() {
+        bail!("couldn't decode audio")
+    }
+    Ok(pcm_bytes
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect())
 }
 
+/// empty if the audio has no embedded cover
 pub fn extract_thumbnail(audio_bytes: &[u8]) -> Result<Vec<u8>> {
-    let (_audio_tfile, audio_tfilepath) = tempfile(audio_bytes)?;
-    Ok(Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
-        .args([
+    let input = AudioInput::new(audio_bytes)?;
+    // with nothing to copy ffmpeg fails, which would be indistinguishable from a real failure
+    if !probe_input(&input)?.contains(": Video: ") {
+        return Ok(vec![]);
+    }
+    Ok(input
+        .checked_output(Command::new(get_command(DEFAULT_FFMPEG_COMMAND)).args([
             "-i",
-            &audio_tfilepath,
+            input.path(),
             "-an",
             "-vcodec",
             "copy",
             "-f",
             "mjpeg",
             "-",
-        ])
-        .creation_flags(WIN_FLAG_CREATE_NO_WINDOW)
-        .output()?
+        ]))?
         .stdout)
 }
 
 pub fn extract_metadata(audio_bytes: &[u8]) -> Result<Value> {
-    let (_audio_tfile, audio_tfilepath) = tempfile(audio_bytes)?;
+    let input = AudioInput::new(audio_bytes)?;
     let raw_metadata = String::from_utf8(
-        Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
-            .args([
+        input
+            .checked_output(Command::new(get_command(DEFAULT_FFMPEG_COMMAND)).args([
                 "-i",
-                &audio_tfilepath,
+                input.path(),
                 "-loglevel",
                 "panic",
                 "-hide_banner",
                 "-f",
                 "ffmetadata",
                 "-",
-            ])
-            .creation_flags(WIN_FLAG_CREATE_NO_WINDOW)
-            .output()?
+            ]))?
             .stdout,
     )?;
 
+    Ok(Value::Object(parse_ffmetadata(&raw_metadata)))
+}
+
+/// reads the global `key=value` entries of an ffmetadata file,
+/// a `\` escapes the next character, including newlines, `=`, `;`, `#` and itself
+fn parse_ffmetadata(raw_metadata: &str) -> Map<String, Value> {
+    // (character, was escaped)
+    let mut lines: Vec<Vec<(char, bool)>> = vec![vec![]];
+    let mut chars = raw_metadata.chars();
+    while let Some(character) = chars.next() {
+        match character {
+            '\\' => {
+                if let Some(escaped_char) = chars.next() {
+                    lines.last_mut().unwrap().push((escaped_char, true));
+                }
+            }
+            '\n' => lines.push(vec![]),
+            '\r' => (),
+            _ => lines.last_mut().unwrap().push((character, false)),
+        }
+    }
+
     let mut map = Map::new();
-    let metadata_re = Regex::new(r"(\S+)=(\S+)")?;
-    for cap in metadata_re.captures_iter(&raw_metadata) {
-        map.insert(cap[1].to_string(), Value::String(cap[2].to_string()));
+    for line in lines {
+        match line.first() {
+            None | Some((';' | '#', false)) => continue,
+            // stream and chapter sections come after the global metadata
+            Some(('[', false)) => break,
+            _ => (),
+        }
+        let Some(separator) = line.iter().position(|&entry| entry == ('=', false)) else {
+            continue;
+        };
+        let key: String = line[..separator].iter().map(|(character, _)| character).collect();
+        let value: String = line[separator + 1..].iter().map(|(character, _)| character).collect();
+        map.insert(key, Value::String(value));
     }
-    Ok(Value::Object(map))
+    map
 }
 
-pub fn get_average_volume(audio_bytes: &[u8]) -> Result<f32> {
-    let (_audio_tfile, audio_tfilepath) = tempfile(audio_bytes)?;
+/// the mean and max volume in dB
+pub fn get_volume_levels(audio_bytes: &[u8]) -> Result<(f32, f32)> {
+    let input = AudioInput::new(audio_bytes)?;
     let output_string = String::from_utf8(
-        Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
-            .args([
+        input
+            .checked_output(Command::new(get_command(DEFAULT_FFMPEG_COMMAND)).args([
                 "-i",
-                &audio_tfilepath,
+                input.path(),
                 "-hide_banner",
                 "-af",
                 "volumedetect",
@@ -142,46 +966,320 @@ pub fn get_average_volume(audio_bytes: &[u8]) -> Result<f32> {
                 "-f",
                 "null",
                 "-",
-            ])
-            .creation_flags(WIN_FLAG_CREATE_NO_WINDOW)
-            .output()?
+            ]))?
             .stderr,
     )?;
-    let volume_re = Regex::new(r"mean_volume:\s(\S+)\s")?;
-    let mut captures = volume_re.captures_iter(&output_string);
-    Ok(captures.next().context("couldn't get volume")?[1].parse::<f32>()?)
+    let volume_level = |name: &str| -> Result<f32> {
+        let volume_re = Regex::new(&format!(r"{name}:\s(\S+)\s"))?;
+        let mut captures = volume_re.captures_iter(&output_string);
+        Ok(captures.next().context("couldn't get volume")?[1].parse::<f32>()?)
+    };
+    Ok((volume_level("mean_volume")?, volume_level("max_volume")?))
 }
 
-pub fn apply_volume_offset(audio_bytes: &[u8], offset: f32) -> Result<Vec<u8>> {
-    let (_audio_tfile, audio_tfilepath) = tempfile(&audio_bytes)?;
-    Ok(Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
-        .args([
-            "-i",
-            &audio_tfilepath,
-            "-af",
-            &format!("volume={offset}dB"),
-            "-f",
-            FFMPEG_AUDIO_FORMAT,
-            "-",
-        ])
-        .creation_flags(WIN_FLAG_CREATE_NO_WINDOW)
-        .output()?
-        .stdout)
+pub fn apply_volume_offset(
+    audio_bytes: &[u8],
+    offset: f32,
+    encode_options: &EncodeOptions,
+) -> Result<Vec<u8>> {
+    let input = AudioInput::new(audio_bytes)?;
+    encode_output(
+        Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
+            .args(["-i", input.path(), "-af", &format!("volume={offset}dB")])
+            .args(encode_options.args()),
+        &input,
+        encode_options.format,
+    )
 }
 
-pub fn download_thumbnail(query_url: &String) -> Result<Output> {
-    Ok(Command::new(get_command(DEFAULT_CURL_COMMAND))
-        .args([query_url, "-o", "-"])
-        .creation_flags(WIN_FLAG_CREATE_NO_WINDOW)
-        .output()?)
+// loudnorm resamples to 192kHz internally, so the output rate is pinned back down
+const LOUDNORM_SAMPLE_RATE: u32 = 48000;
+const LOUDNORM_TRUE_PEAK: f32 = -1.5;
+const LOUDNORM_LOUDNESS_RANGE: f32 = 11.;
+
+/// two pass ebu r128 normalization, the first pass measures and the second applies linearly
+pub fn normalize_loudness(
+    audio_bytes: &[u8],
+    target_lufs: f32,
+    encode_options: &EncodeOptions,
+) -> Result<Vec<u8>> {
+    let input = AudioInput::new(audio_bytes)?;
+    let loudnorm_target =
+        format!("I={target_lufs}:TP={LOUDNORM_TRUE_PEAK}:LRA={LOUDNORM_LOUDNESS_RANGE}");
+
+    let measure_output = String::from_utf8(
+        input
+            .checked_output(Command::new(get_command(DEFAULT_FFMPEG_COMMAND)).args([
+                "-i",
+                input.path(),
+                "-hide_banner",
+                "-af",
+                &format!("loudnorm={loudnorm_target}:print_format=json"),
+                "-vn",
+                "-sn",
+                "-dn",
+                "-f",
+                "null",
+                "-",
+            ]))?
+            .stderr,
+    )?;
+    // the measurements are the last json object in the log
+    let measurements: Value = serde_json::from_str(
+        measure_output
+            .rfind('{')
+            .map(|start| &measure_output[start..])
+            .context("couldn't measure loudness")?,
+    )?;
+    let measured = |field: &str| json_read(&measurements, field);
+
+    encode_output(
+        Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
+            .args([
+                "-i",
+                input.path(),
+                "-af",
+                &format!(
+                    "loudnorm={loudnorm_target}:measured_I={}:measured_TP={}:measured_LRA={}:\
+                     measured_thresh={}:offset={}:linear=true",
+                    measured("input_i"),
+                    measured("input_tp"),
+                    measured("input_lra"),
+                    measured("input_thresh"),
+                    measured("target_offset"),
+                ),
+                "-ar",
+                &LOUDNORM_SAMPLE_RATE.to_string(),
+            ])
+            .args(encode_options.args()),
+        &input,
+        encode_options.format,
+    )
+}
+
+pub fn trim_audio(
+    audio_bytes: &[u8],
+    start_secs: f32,
+    end_secs: f32,
+    encode_options: &EncodeOptions,
+) -> Result<Vec<u8>> {
+    let input = AudioInput::new(audio_bytes)?;
+    encode_output(
+        Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
+            .args(["-i", input.path()])
+            .args(["-ss", &start_secs.to_string(), "-to", &end_secs.to_string()])
+            .args(encode_options.args()),
+        &input,
+        encode_options.format,
+    )
+}
+
+/// runs `name` with its version flag and returns the first line it prints
+pub fn check_command(name: &str) -> Result<String> {
+    let version_flag = if name == DEFAULT_FFMPEG_COMMAND {
+        "-version"
+    } else {
+        "--version"
+    };
+    let command = get_command(name);
+    let output = Command::new(&command)
+        .arg(version_flag)
+        .tracked_output()
+        .with_context(|| format!("couldn't run {command}"))?;
+    if !output.status.success() {
+        bail!("{command} exited with {}", output.status)
+    }
+    String::from_utf8(output.stdout)?
+        .lines()
+        .next()
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .with_context(|| format!("{command} didn't print a version"))
+}
+
+pub const YT_DL_LATEST_RELEASE_URL: &str =
+    "https://api.github.com/repos/yt-dlp/yt-dlp/releases/latest";
+
+// musicbrainz turns away clients that don't say who they are
+const HTTP_USER_AGENT: &str = concat!(
+    env!("CARGO_PKG_NAME"),
+    "/",
+    env!("CARGO_PKG_VERSION"),
+    " ( https://github.com/n00kii/songdl )"
+);
+
+/// GET that follows redirects, anything but a 200 is an error
+pub fn http_get(url: &str, proxy_url: Option<&str>) -> Result<Vec<u8>> {
+    let mut agent = ureq::AgentBuilder::new().user_agent(HTTP_USER_AGENT);
+    if let Some(proxy_url) = proxy_url {
+        agent = agent.proxy(ureq::Proxy::new(proxy_url).context("invalid proxy url")?);
+    }
+    let response = agent.build().get(url).call()?;
+    if response.status() != 200 {
+        bail!("unexpected response {} from {url}", response.status())
+    }
+    let mut bytes = vec![];
+    response.into_reader().read_to_end(&mut bytes)?;
+    Ok(bytes)
+}
+
+pub fn latest_ytdl_version(proxy_url: Option<&str>) -> Result<String> {
+    let release: Value = serde_json::from_slice(&http_get(YT_DL_LATEST_RELEASE_URL, proxy_url)?)?;
+    Ok(release
+        .get("tag_name")
+        .and_then(|tag| tag.as_str())
+        .context("couldn't read latest release")?
+        .to_string())
+}
+
+/// runs yt-dl's self-updater and returns its last line of output
+pub fn update_ytdl() -> Result<String> {
+    let output = Command::new(get_command(DEFAULT_YT_DL_COMMAND))
+        .arg("-U")
+        .tracked_output()?;
+    let log = String::from_utf8_lossy(if output.status.success() {
+        &output.stdout
+    } else {
+        &output.stderr
+    })
+    .to_string();
+    let last_line = log
+        .lines()
+        .rev()
+        .find(|line| !line.trim().is_empty())
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+    if !output.status.success() {
+        bail!("{last_line}")
+    }
+    Ok(last_line)
+}
+
+/// editors that pass the file on to an instance that's already open exit sooner than this
+const EDITOR_HANDOFF_WINDOW: Duration = Duration::from_secs(3);
+/// how long a handed off file has to stay untouched after a save before it's read back
+const EDIT_SETTLE_INTERVAL: Duration = Duration::from_secs(1);
+/// a handed off file that's closed without saving gives no sign, so the watch gives up after this
+const EDIT_WATCH_LIMIT: Duration = Duration::from_secs(10 * 60);
+const EDITOR_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// opens `filepath` in the editor and waits for it, returning whether the file was saved.
+/// editors that hand the file to a running instance return at once, so then the file is
+/// watched until it's saved, the wait is cancelled or `EDIT_WATCH_LIMIT` passes unsaved
+pub fn open_in_editor(editor_path: &str, filepath: &str) -> Result<bool> {
+    let modified = || std::fs::metadata(filepath).and_then(|metadata| metadata.modified()).ok();
+    let modified_before = modified();
+
+    let child = configure_command(Command::new(editor_path).arg(filepath))
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()?;
+    let started = Instant::now();
+    let child_id = child.id();
+    let child = Arc::new(Mutex::new(child));
+    running_children()
+        .lock()
+        .insert(child_id, (child.clone(), current_cancel_token()));
+
+    let exited = (|| loop {
+        check_cancelled()?;
+        if let Some(status) = child.lock().try_wait()? {
+            if !status.success() {
+                bail!("editor exited with {status}")
+            }
+            break anyhow::Ok(());
+        }
+        thread::sleep(EDITOR_POLL_INTERVAL);
+    })();
+    running_children().lock().remove(&child_id);
+    exited?;
+    if started.elapsed() >= EDITOR_HANDOFF_WINDOW {
+        return Ok(modified() != modified_before);
+    }
+
+    // handed off, so it's done once a save has settled
+    let watch_started = Instant::now();
+    let mut last_modified = modified_before;
+    let mut unchanged_since = Instant::now();
+    loop {
+        check_cancelled()?;
+        let modified_now = modified();
+        if modified_now == modified_before && watch_started.elapsed() >= EDIT_WATCH_LIMIT {
+            return Ok(false);
+        }
+        if modified_now != last_modified {
+            last_modified = modified_now;
+            unchanged_since = Instant::now();
+        } else if modified_now != modified_before
+            && unchanged_since.elapsed() >= EDIT_SETTLE_INTERVAL
+        {
+            return Ok(true);
+        }
+        thread::sleep(EDITOR_POLL_INTERVAL);
+    }
+}
+
+/// opens the os file browser with `path` selected where the platform supports it
+pub fn reveal_in_file_browser(path: &Path) -> Result<()> {
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = Command::new("explorer");
+        // explorer wants the path glued to the flag
+        command.arg(format!("/select,{}", path.display()));
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    };
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let mut command = {
+        let mut command = Command::new("xdg-open");
+        command.arg(path.parent().context("file has no folder")?);
+        command
+    };
+    configure_command(&mut command).spawn()?;
+    Ok(())
+}
+
+/// an empty buffer means there's no usable thumbnail
+pub fn download_thumbnail(query_url: &String, proxy_url: Option<&str>) -> Result<Vec<u8>> {
+    check_cancelled()?;
+    if let Some(proxy_url) = proxy_url {
+        // a bad proxy shouldn't pass for a missing thumbnail
+        ureq::Proxy::new(proxy_url).context("invalid proxy url")?;
+    }
+    match http_get(query_url, proxy_url) {
+        Ok(bytes) => Ok(bytes),
+        // left for the caller to retry
+        Err(error) if is_transient_error(&error) => Err(error),
+        Err(_) => Ok(vec![]),
+    }
 }
 
-pub fn write_cover_to_audio(audio_bytes: &[u8], cover_bytes: &[u8]) -> Result<Vec<u8>> {
+pub fn write_cover_to_audio(
+    audio_bytes: &[u8],
+    cover_bytes: &[u8],
+    format: AudioFormat,
+) -> Result<Vec<u8>> {
+    if !format.supports_cover() || cover_bytes.is_empty() {
+        return Ok(audio_bytes.to_vec());
+    }
+    // only one input can be piped, so both go through temp files
     let (_cover_tfile, cover_tfilepath) = tempfile(cover_bytes)?;
     let (_audio_tfile, audio_tfilepath) = tempfile(audio_bytes)?;
-    let (mut final_audio_tfile, final_audio_tfilepath) = tempfile(&[])?;
+    let (_final_audio_tfile, final_audio_tfilepath) =
+        tempfile_with_suffix(&[], format.extension())?;
+
+    let format_args: &[&str] = match format {
+        AudioFormat::Mp3 => &["-id3v2_version", "3"],
+        _ => &["-disposition:v", "attached_pic"],
+    };
 
-    let mut final_audio_bytes = vec![];
     Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
         .args([
             "-i",
@@ -189,34 +1287,32 @@ pub fn write_cover_to_audio(audio_bytes: &[u8], cover_bytes: &[u8]) -> Result<Ve
             "-i",
             &cover_tfilepath,
             "-map",
-            "0:0",
+            "0:a",
             "-map",
             "1:0",
             "-c",
             "copy",
-            "-id3v2_version",
-            "3",
-            "-y",
-            "-f",
-            FFMPEG_AUDIO_FORMAT,
-            &final_audio_tfilepath,
         ])
-        .creation_flags(WIN_FLAG_CREATE_NO_WINDOW)
-        .output()?;
-    final_audio_tfile.read_to_end(&mut final_audio_bytes)?;
-    Ok(final_audio_bytes)
+        .args(format_args)
+        .args(["-y", "-f", format.muxer(), &final_audio_tfilepath])
+        .checked_output()
+        .context("couldn't embed the cover")?;
+    // read back by path like `encode_output`, the handle that created the file is never read
+    Ok(std::fs::read(&final_audio_tfilepath)?)
 }
 
 pub fn write_metadata_to_audio(
     audio_bytes: &[u8],
     metadata: Vec<(String, String)>,
+    format: AudioFormat,
 ) -> Result<Vec<u8>> {
-    let (_audio_tfile, audio_tfilepath) = tempfile(&audio_bytes)?;
-    Ok(Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
-        .args(generate_args_from_metadata(audio_tfilepath, metadata))
-        .creation_flags(WIN_FLAG_CREATE_NO_WINDOW)
-        .output()?
-        .stdout)
+    let input = AudioInput::new(audio_bytes)?;
+    encode_output(
+        Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
+            .args(generate_args_from_metadata(input.path().to_string(), metadata)),
+        &input,
+        format,
+    )
 }
 
 fn generate_args_from_metadata(filepath: String, metadata: Vec<(String, String)>) -> Vec<String> {
@@ -236,13 +1332,96 @@ fn generate_args_from_metadata(filepath: String, metadata: Vec<(String, String)>
     ]
     .into_iter()
     .chain(inner_args.into_iter())
-    .chain(
-        vec![
-            String::from("-f"),
-            String::from(FFMPEG_AUDIO_FORMAT),
-            String::from("-"),
-        ]
-        .into_iter(),
-    )
     .collect::<Vec<_>>()
 }
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// the tests that run ffmpeg are skipped without it
+    pub(crate) fn ffmpeg_available() -> bool {
+        Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
+            .arg("-version")
+            .output()
+            .is_ok_and(|output| output.status.success())
+    }
+
+    /// a second of a stereo sine in `muxer`'s container
+    pub(crate) fn sine_audio(muxer: &str) -> Vec<u8> {
+        Command::new(get_command(DEFAULT_FFMPEG_COMMAND))
+            .args(["-f", "lavfi", "-i", "sine=frequency=440:duration=1", "-ac", "2"])
+            .args(["-f", muxer, "-"])
+            .checked_output()
+            .unwrap()
+            .stdout
+    }
+
+    fn jpeg_cover() -> Vec<u8> {
+        let mut cover_bytes = vec![];
+        image::DynamicImage::ImageRgb8(image::RgbImage::new(8, 8))
+            .write_to(&mut std::io::Cursor::new(&mut cover_bytes), image::ImageFormat::Jpeg)
+            .unwrap();
+        cover_bytes
+    }
+
+    fn read<'a>(map: &'a Map<String, Value>, key: &str) -> &'a str {
+        map.get(key).and_then(Value::as_str).unwrap_or_default()
+    }
+
+    #[test]
+    fn transient_errors_are_told_from_permanent_ones() {
+        let transient = |message: &str| is_transient_error(&anyhow::anyhow!("ERROR: {message}"));
+        assert!(transient("Unable to download webpage: HTTP Error 503: Service Unavailable"));
+        assert!(transient("Unable to download webpage: HTTP Error 429: Too Many Requests"));
+        assert!(transient("unable to download video data: <urlopen error timed out>"));
+        assert!(!transient("[youtube] dQw4w9WgXcQ: Video unavailable"));
+        assert!(!transient("Requested format is not available"));
+        assert!(!is_transient_error(&anyhow::Error::new(Cancelled)));
+    }
+
+    #[test]
+    fn parse_ffmetadata_reads_escaped_and_continued_values() {
+        let raw_metadata = concat!(
+            ";FFMETADATA1\n",
+            "title=Some Long Title (Live at the Place)\n",
+            "artist=first\\=second\\;third\\#fourth\n",
+            "# a comment\n",
+            "lyrics=first line\\\n",
+            "second line\\\n",
+            "third line\n",
+            "comment=back\\\\slash\r\n",
+            "[STREAM]\n",
+            "title=stream title\n",
+        );
+        let map = parse_ffmetadata(raw_metadata);
+        assert_eq!(read(&map, "title"), "Some Long Title (Live at the Place)");
+        assert_eq!(read(&map, "artist"), "first=second;third#fourth");
+        assert_eq!(read(&map, "lyrics"), "first line\nsecond line\nthird line");
+        assert_eq!(read(&map, "comment"), "back\\slash");
+        assert_eq!(map.len(), 4);
+    }
+
+    #[test]
+    fn write_cover_to_audio_keeps_a_complete_mp3() {
+        if !ffmpeg_available() {
+            return;
+        }
+        let metadata = vec![(String::from("title"), String::from("cover test"))];
+        let audio_bytes =
+            write_metadata_to_audio(&sine_audio("mp3"), metadata, AudioFormat::Mp3).unwrap();
+        let audio_bytes =
+            write_cover_to_audio(&audio_bytes, &jpeg_cover(), AudioFormat::Mp3).unwrap();
+
+        assert!(audio_bytes.starts_with(b"ID3"));
+        assert!(!extract_thumbnail(&audio_bytes).unwrap().is_empty());
+        assert_eq!(
+            json_read(&extract_metadata(&audio_bytes).unwrap(), "title"),
+            "cover test"
+        );
+        // the whole second decodes, so nothing was cut off while embedding
+        let frame_count = decode_to_pcm(&audio_bytes).unwrap().len() / 2;
+        let decoded_secs = frame_count as f32 / PCM_PREVIEW_SAMPLE_RATE as f32;
+        assert!(decoded_secs > 0.95, "decoded {decoded_secs}s");
+    }
+}
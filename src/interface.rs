@@ -1,20 +1,25 @@
-use std::path::PathBuf;
-
-use crate::{
-    app::{self, App},
-    song::{Origin, WAVEFORM_LENGTH},
+use std::{fmt::Display, path::PathBuf};
+
+use crate::app::{self, App, BatchReport, QueueEnd, QueueStatus};
+use songdl::{
+    command::{
+        is_valid_rate_limit, AudioFormat, Bitrate, SampleFormat, COOKIE_BROWSERS,
+        DEFAULT_FFMPEG_COMMAND, DEFAULT_YT_DL_COMMAND, YT_DL_FORMAT_SELECTOR,
+    },
+    song::{remove_characters, CoverFit, FolderStructure, LoudnessMode, Origin, WaveformMode},
 };
 use egui::{
-    pos2, vec2, Align2, Button, CentralPanel, Color32, Context, FontData, FontFamily, FontId,
-    Image, Label, Layout, Rect, Response, RichText, Rounding, Sense, Slider, Spinner, Stroke,
-    Style, TextEdit, TopBottomPanel, Ui, Vec2,
+    pos2, vec2, Align2, Button, CentralPanel, Color32, ComboBox, Context, FontData, FontFamily,
+    FontId, Image, Key, Label, Layout, Pos2, ProgressBar, Rect, Response, RichText, Rounding, Sense,
+    Slider, Spinner, Stroke, Style, TextEdit, TopBottomPanel, Ui, Vec2, Visuals,
 };
 use egui_extras::{Column, Size, StripBuilder, TableBuilder};
+use serde::{Deserialize, Serialize};
 
 #[macro_export]
 macro_rules! iconst {
     ($name:ident) => {
-        crate::interface::constants::$name
+        $crate::interface::constants::$name
     };
 }
 
@@ -28,6 +33,7 @@ macro_rules! label {
 pub enum InterfacePage {
     #[default]
     Downloader,
+    History,
     Settings,
 }
 
@@ -43,6 +49,11 @@ fn draw_nav_panel(app: &mut App, ctx: &Context) {
                 InterfacePage::Downloader,
                 label!("download", DOWNLOADER_ICON),
             );
+            ui.selectable_value(
+                &mut app.current_page,
+                InterfacePage::History,
+                label!("history", HISTORY_ICON),
+            );
             ui.selectable_value(
                 &mut app.current_page,
                 InterfacePage::Settings,
@@ -71,6 +82,7 @@ fn draw_settings(app: &mut App, ui: &mut Ui) {
                 label: &str,
                 field_opt: &mut Option<String>,
                 is_file: bool,
+                check: Option<&anyhow::Result<String>>,
                 updated: &mut bool,
             ) {
                 body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
@@ -94,6 +106,23 @@ fn draw_settings(app: &mut App, ui: &mut Ui) {
                             ui.add_enabled_ui(field_enabled, |ui| {
                                 ui.label(label);
                             });
+                            match check {
+                                Some(Ok(version)) => {
+                                    ui.label(
+                                        RichText::new(iconst!(DONE_ICON))
+                                            .color(ui.visuals().selection.stroke.color),
+                                    )
+                                    .on_hover_text(version);
+                                }
+                                Some(Err(error)) => {
+                                    ui.label(
+                                        RichText::new(iconst!(FAILED_ICON))
+                                            .color(iconst!(ERROR_COLOR)),
+                                    )
+                                    .on_hover_text(error.to_string());
+                                }
+                                None => (),
+                            }
                         });
                     });
                     row.col(|ui| {
@@ -122,6 +151,7 @@ fn draw_settings(app: &mut App, ui: &mut Ui) {
                 "default save directory",
                 &mut app.settings.default_save_directory,
                 false,
+                None,
                 &mut updated,
             );
             path_field(
@@ -129,6 +159,7 @@ fn draw_settings(app: &mut App, ui: &mut Ui) {
                 "ffmpeg location",
                 &mut app.settings.ffmpeg_path,
                 true,
+                app.command_checks.get(DEFAULT_FFMPEG_COMMAND),
                 &mut updated,
             );
             path_field(
@@ -136,44 +167,699 @@ fn draw_settings(app: &mut App, ui: &mut Ui) {
                 "yt-dl location",
                 &mut app.settings.ytdl_path,
                 true,
+                app.command_checks.get(DEFAULT_YT_DL_COMMAND),
+                &mut updated,
+            );
+            path_field(
+                &mut body,
+                "audio editor location",
+                &mut app.settings.audio_editor_path,
+                true,
+                None,
+                &mut updated,
+            );
+            path_field(
+                &mut body,
+                "cookies file",
+                &mut app.settings.cookies_file,
+                true,
+                None,
                 &mut updated,
             );
 
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("proxy");
+                });
+                row.col(|ui| {
+                    let mut proxy_url = app.settings.proxy_url.clone().unwrap_or_default();
+                    let proxy_response = TextEdit::singleline(&mut proxy_url)
+                        .hint_text("http://host:port")
+                        .show(ui)
+                        .response;
+                    if proxy_response.changed() {
+                        app.settings.proxy_url = (!proxy_url.is_empty()).then_some(proxy_url);
+                    }
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("format selector");
+                });
+                row.col(|ui| {
+                    TextEdit::singleline(&mut app.settings.ytdl_format)
+                        .hint_text(YT_DL_FORMAT_SELECTOR)
+                        .show(ui)
+                        .response
+                        .on_hover_text("passed to yt-dl as -f, see its format selection docs");
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("download rate limit");
+                });
+                row.col(|ui| {
+                    let mut rate_limit =
+                        app.settings.download_rate_limit.clone().unwrap_or_default();
+                    let is_valid =
+                        rate_limit.trim().is_empty() || is_valid_rate_limit(rate_limit.trim());
+                    let rate_limit_response = TextEdit::singleline(&mut rate_limit)
+                        .hint_text("unlimited, e.g. 500K or 2M")
+                        .text_color_opt((!is_valid).then_some(ui.visuals().error_fg_color))
+                        .show(ui)
+                        .response;
+                    if rate_limit_response.changed() {
+                        app.settings.download_rate_limit =
+                            (!rate_limit.is_empty()).then_some(rate_limit);
+                    }
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("cookies from browser");
+                });
+                row.col(|ui| {
+                    ComboBox::from_id_source("cookies_from_browser")
+                        .selected_text(
+                            app.settings.cookies_from_browser.as_deref().unwrap_or("none"),
+                        )
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(
+                                &mut app.settings.cookies_from_browser,
+                                None,
+                                "none",
+                            );
+                            for browser in COOKIE_BROWSERS {
+                                ui.selectable_value(
+                                    &mut app.settings.cookies_from_browser,
+                                    Some(browser.to_string()),
+                                    browser,
+                                );
+                            }
+                        });
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("yt-dl version");
+                });
+                row.col(|ui| {
+                    ui.horizontal(|ui| {
+                        if let Some(ytdl_info) = app.ytdl_info.as_ref() {
+                            ui.label(&ytdl_info.installed_version);
+                            if ytdl_info.is_outdated() {
+                                ui.label(
+                                    RichText::new(format!(
+                                        "(latest: {})",
+                                        ytdl_info.latest_version.as_deref().unwrap_or_default()
+                                    ))
+                                    .color(app.settings.theme.accent_color()),
+                                );
+                            }
+                        } else if app.loading_ytdl_info.is_some() {
+                            ui.add(Spinner::new());
+                        } else {
+                            ui.label("unknown");
+                        }
+                        ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                            let busy =
+                                app.loading_ytdl_info.is_some() || app.updating_ytdl.is_some();
+                            if ui.add_enabled(!busy, Button::new("update")).clicked() {
+                                app.update_ytdl();
+                            }
+                            if ui.add_enabled(!busy, Button::new("check")).clicked() {
+                                app.check_ytdl_version(true);
+                            }
+                        });
+                    });
+                });
+            });
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("check yt-dl on startup");
+                });
+                row.col(|ui| {
+                    ui.checkbox(&mut app.settings.check_ytdl_update_on_startup, "")
+                        .on_hover_text("compare the installed yt-dl against the latest release");
+                });
+            });
+
             body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
                 row.col(|ui| {
                     ui.label("playback volume");
                 });
                 row.col(|ui| {
-                    if ui
-                        .add(
+                    let response = ui.add_enabled_ui(app.audio_manager.is_some(), |ui| {
+                        ui.add(
                             Slider::new(&mut app.settings.playback_volume, 0.0..=1.0)
                                 .custom_formatter(|v, _| format!("{}%", (v * 100.) as usize)),
                         )
-                        .changed()
-                    {
+                    });
+                    if response.inner.changed() {
                         let _ = app.apply_playback_volume();
                     }
                 });
             });
 
+            if app.audio_manager.is_none() {
+                body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                    row.col(|ui| {
+                        ui.label("audio output");
+                    });
+                    row.col(|ui| {
+                        if ui
+                            .button("retry audio init")
+                            .on_hover_text("look for an output device again")
+                            .clicked()
+                        {
+                            app.retry_audio_init();
+                        }
+                    });
+                });
+            }
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("theme");
+                });
+                row.col(|ui| {
+                    ui.horizontal(|ui| {
+                        let mut theme_changed = false;
+                        for mode in [ThemeMode::Dark, ThemeMode::Light] {
+                            theme_changed |= ui
+                                .selectable_value(
+                                    &mut app.settings.theme.mode,
+                                    mode,
+                                    mode.to_string(),
+                                )
+                                .changed();
+                        }
+                        ui.separator();
+                        theme_changed |= ui
+                            .color_edit_button_srgb(&mut app.settings.theme.accent)
+                            .on_hover_text("accent color")
+                            .changed();
+                        if theme_changed {
+                            load_style(ui.ctx(), &app.settings.theme);
+                        }
+                    });
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("fade duration");
+                });
+                row.col(|ui| {
+                    ui.add(Slider::new(&mut app.settings.fade_duration_ms, 0..=2000).suffix(" ms"));
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("loudness mode");
+                });
+                row.col(|ui| {
+                    ComboBox::from_id_source("loudness_mode")
+                        .selected_text(app.settings.loudness_mode.to_string())
+                        .show_ui(ui, |ui| {
+                            for loudness_mode in LoudnessMode::ALL {
+                                ui.selectable_value(
+                                    &mut app.settings.loudness_mode,
+                                    loudness_mode,
+                                    loudness_mode.to_string(),
+                                );
+                            }
+                        });
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("loudness target");
+                });
+                row.col(|ui| {
+                    ui.add(
+                        Slider::new(&mut app.settings.target_lufs, -30.0..=-5.0).suffix(" LUFS"),
+                    );
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("concurrent downloads");
+                });
+                row.col(|ui| {
+                    ui.add(Slider::new(&mut app.settings.max_concurrent_downloads, 1..=8))
+                        .on_hover_text("how many playlist entries are downloaded at once");
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("download attempts");
+                });
+                row.col(|ui| {
+                    ui.add(Slider::new(&mut app.settings.max_download_attempts, 1..=5))
+                        .on_hover_text("network errors are retried, unavailable videos aren't");
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("folder structure");
+                });
+                row.col(|ui| {
+                    ComboBox::from_id_source("folder_structure")
+                        .selected_text(app.settings.folder_structure.to_string())
+                        .show_ui(ui, |ui| {
+                            for folder_structure in FolderStructure::ALL {
+                                ui.selectable_value(
+                                    &mut app.settings.folder_structure,
+                                    folder_structure,
+                                    folder_structure.to_string(),
+                                );
+                            }
+                        });
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("tag encoder");
+                });
+                row.col(|ui| {
+                    ui.checkbox(&mut app.settings.tag_provenance, "").on_hover_text(
+                        "write the songdl version and the encoding date into saved songs",
+                    );
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("cover fit");
+                });
+                row.col(|ui| {
+                    ComboBox::from_id_source("cover_fit")
+                        .selected_text(app.settings.cover_fit.to_string())
+                        .show_ui(ui, |ui| {
+                            for cover_fit in CoverFit::ALL {
+                                ui.selectable_value(
+                                    &mut app.settings.cover_fit,
+                                    cover_fit,
+                                    cover_fit.to_string(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text("only affects the preview, the full image is embedded");
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("at the end of the queue");
+                });
+                row.col(|ui| {
+                    ComboBox::from_id_source("queue_end")
+                        .selected_text(app.settings.queue_end.to_string())
+                        .show_ui(ui, |ui| {
+                            for queue_end in QueueEnd::ALL {
+                                ui.selectable_value(
+                                    &mut app.settings.queue_end,
+                                    queue_end,
+                                    queue_end.to_string(),
+                                );
+                            }
+                        });
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("autoplay queue");
+                });
+                row.col(|ui| {
+                    ui.checkbox(&mut app.settings.autoplay_queue, "")
+                        .on_hover_text("start playing when moving to the next or previous entry");
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("advance queue automatically");
+                });
+                row.col(|ui| {
+                    ui.checkbox(&mut app.settings.auto_advance_queue, "")
+                        .on_hover_text("play the next entry when a song finishes");
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("audio format");
+                });
+                row.col(|ui| {
+                    ComboBox::from_id_source("audio_format")
+                        .selected_text(app.settings.audio_format.to_string())
+                        .show_ui(ui, |ui| {
+                            for audio_format in AudioFormat::ALL {
+                                ui.selectable_value(
+                                    &mut app.settings.audio_format,
+                                    audio_format,
+                                    audio_format.to_string(),
+                                );
+                            }
+                        });
+                });
+            });
+            if app.settings.audio_format.supports_sample_format() {
+                body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                    row.col(|ui| {
+                        ui.label("bit depth");
+                    });
+                    row.col(|ui| {
+                        ComboBox::from_id_source("sample_format")
+                            .selected_text(app.settings.sample_format.to_string())
+                            .show_ui(ui, |ui| {
+                                for sample_format in SampleFormat::ALL {
+                                    ui.selectable_value(
+                                        &mut app.settings.sample_format,
+                                        sample_format,
+                                        sample_format.to_string(),
+                                    );
+                                }
+                            });
+                    });
+                });
+            }
+            if app.settings.audio_format.is_lossy() {
+                body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                    row.col(|ui| {
+                        ui.label("bitrate");
+                    });
+                    row.col(|ui| {
+                        ComboBox::from_id_source("bitrate")
+                            .selected_text(app.settings.bitrate.to_string())
+                            .show_ui(ui, |ui| {
+                                for bitrate in Bitrate::ALL {
+                                    ui.selectable_value(
+                                        &mut app.settings.bitrate,
+                                        bitrate,
+                                        bitrate.to_string(),
+                                    );
+                                }
+                            });
+                    });
+                });
+            }
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("keep original format");
+                });
+                row.col(|ui| {
+                    ui.checkbox(&mut app.settings.keep_original_format, "")
+                        .on_hover_text(
+                            "copy the source stream without re-encoding when possible, \
+                             other lossless sources become flac, \
+                             the audio format above is used otherwise",
+                        );
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("clean titles");
+                });
+                row.col(|ui| {
+                    ui.checkbox(&mut app.settings.clean_titles, "")
+                        .on_hover_text("strip junk from titles of loaded songs");
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("title junk patterns");
+                });
+                row.col(|ui| {
+                    // split without trimming so the text round-trips while typing
+                    let mut junk_patterns = app.settings.title_junk_patterns.join(",");
+                    let junk_patterns_response = TextEdit::singleline(&mut junk_patterns)
+                        .hint_text("comma separated")
+                        .show(ui)
+                        .response
+                        .on_hover_text("bracketed title groups with any of these words are cut");
+                    if junk_patterns_response.changed() {
+                        app.settings.title_junk_patterns =
+                            junk_patterns.split(',').map(String::from).collect();
+                    }
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("cache waveform peaks");
+                });
+                row.col(|ui| {
+                    ui.checkbox(&mut app.settings.cache_waveform_peaks, "")
+                        .on_hover_text(
+                            "store the waveform of local files next to them as a .peaks file",
+                        );
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("waveform mode");
+                });
+                row.col(|ui| {
+                    ComboBox::from_id_source("waveform_mode")
+                        .selected_text(app.settings.waveform_mode.to_string())
+                        .show_ui(ui, |ui| {
+                            for waveform_mode in WaveformMode::ALL {
+                                ui.selectable_value(
+                                    &mut app.settings.waveform_mode,
+                                    waveform_mode,
+                                    waveform_mode.to_string(),
+                                );
+                            }
+                        })
+                        .response
+                        .on_hover_text("peak shows transients, rms shows perceived loudness");
+                });
+            });
+
+            body.row(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                row.col(|ui| {
+                    ui.label("settings file");
+                });
+                row.col(|ui| {
+                    ui.horizontal(|ui| {
+                        if ui.button(label!("export", EXPORT_ICON)).clicked() {
+                            if let Some(export_path) = rfd::FileDialog::new()
+                                .add_filter("toml", &["toml"])
+                                .set_file_name(app::SETTINGS_FILENAME)
+                                .save_file()
+                            {
+                                app.export_settings(export_path);
+                            }
+                        }
+                        if ui.button(label!("import", IMPORT_ICON)).clicked() {
+                            if let Some(import_path) =
+                                rfd::FileDialog::new().add_filter("toml", &["toml"]).pick_file()
+                            {
+                                app.import_settings(ui.ctx(), import_path);
+                            }
+                        }
+                    });
+                });
+            });
+
             if updated {
                 app.read_config();
             }
         });
 }
 
+fn draw_history(app: &mut App, ui: &mut Ui) {
+    draw_recent_downloads(app, ui);
+    spacer(ui);
+
+    let mut export_path = None;
+    {
+        let mut report = app.report.lock();
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label("last batch");
+                ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("clear").clicked() {
+                        *report = BatchReport::default();
+                    }
+                    if ui.button(label!("export", EXPORT_ICON)).clicked() {
+                        export_path = rfd::FileDialog::new()
+                            .add_filter("csv", &["csv"])
+                            .add_filter("json", &["json"])
+                            .save_file();
+                    }
+                });
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                ui.label(format!("total: {}", report.entries.len()));
+                ui.separator();
+                ui.label(format!("completed: {}", report.completed()));
+                ui.separator();
+                ui.label(format!("failed: {}", report.failed()));
+                ui.separator();
+                ui.label(format!(
+                    "size: {:.2} MB",
+                    report.total_bytes() as f32 / 1_000_000.
+                ));
+                ui.separator();
+                ui.label(format!("elapsed: {:.1}s", report.elapsed().as_secs_f32()));
+            });
+        });
+        spacer(ui);
+        ui.push_id("batch_table", |ui| {
+            TableBuilder::new(ui)
+                .striped(true)
+                .column(Column::initial(250.).clip(true))
+                .column(Column::exact(80.))
+                .column(Column::remainder().clip(true))
+                .header(iconst!(DETAILS_ROW_HEIGHT), |mut row| {
+                    row.col(|ui| {
+                        ui.label("source");
+                    });
+                    row.col(|ui| {
+                        ui.label("size");
+                    });
+                    row.col(|ui| {
+                        ui.label("status");
+                    });
+                })
+                .body(|body| {
+                    body.rows(
+                        iconst!(DETAILS_ROW_HEIGHT),
+                        report.entries.len(),
+                        |index, mut row| {
+                            let entry = &report.entries[index];
+                            row.col(|ui| {
+                                ui.label(if entry.title.is_empty() {
+                                    &entry.source_url
+                                } else {
+                                    &entry.title
+                                })
+                                .on_hover_text(&entry.source_url);
+                            });
+                            row.col(|ui| {
+                                ui.label(format!("{:.2} MB", entry.bytes as f32 / 1_000_000.));
+                            });
+                            row.col(|ui| {
+                                if let Some(error) = entry.error.as_ref() {
+                                    ui.label(
+                                        RichText::new(label!("failed", FAILED_ICON))
+                                            .color(iconst!(ERROR_COLOR)),
+                                    )
+                                    .on_hover_text(error);
+                                } else {
+                                    ui.label(label!("done", DONE_ICON));
+                                }
+                            });
+                        },
+                    );
+                });
+        });
+    }
+    if let Some(export_path) = export_path {
+        app.export_report(export_path);
+    }
+}
+
+fn draw_recent_downloads(app: &mut App, ui: &mut Ui) {
+    let mut requery_url = None;
+    {
+        let mut history = app.history.lock();
+        ui.group(|ui| {
+            ui.horizontal(|ui| {
+                ui.label("recent downloads");
+                ui.with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("clear").clicked() {
+                        history.entries.clear();
+                    }
+                });
+            });
+        });
+        spacer(ui);
+        ui.push_id("history_table", |ui| {
+            TableBuilder::new(ui)
+                .striped(true)
+                .max_scroll_height(iconst!(HISTORY_TABLE_HEIGHT))
+                .column(Column::initial(250.).clip(true))
+                .column(Column::initial(150.).clip(true))
+                .column(Column::remainder())
+                .body(|body| {
+                    body.rows(
+                        iconst!(DETAILS_ROW_HEIGHT),
+                        history.entries.len(),
+                        |index, mut row| {
+                            let entry = &history.entries[index];
+                            row.col(|ui| {
+                                let title_response = ui
+                                    .add(Label::new(&entry.title).sense(Sense::click()))
+                                    .on_hover_text(format!(
+                                        "{}\nclick to query again",
+                                        entry.source_url
+                                    ));
+                                if title_response.clicked() {
+                                    requery_url = Some(entry.source_url.clone());
+                                }
+                            });
+                            row.col(|ui| {
+                                ui.label(&entry.artist);
+                            });
+                            row.col(|ui| {
+                                ui.label(entry.formatted_timestamp())
+                                    .on_hover_text(&entry.save_path);
+                            });
+                        },
+                    );
+                });
+        });
+    }
+    if let Some(requery_url) = requery_url {
+        app.query_source(ui.ctx(), requery_url);
+    }
+}
+
+/// what stands in for a missing cover
+fn origin_icon(origin: Origin) -> &'static str {
+    match origin {
+        Origin::YouTube => iconst!(YOUTUBE_ICON),
+        Origin::Soundcloud => iconst!(SOUNDCLOUD_ICON),
+        Origin::Bandcamp => iconst!(BANDCAMP_ICON),
+        Origin::Web => iconst!(WEB_ICON),
+        Origin::Local => iconst!(FOLDER_ICON),
+        Origin::Unknown => "?",
+    }
+}
+
 fn draw_cover_image(app: &mut App, ui: &mut Ui) {
     let image_size = [iconst!(COVER_SIZE); 2];
     let cover_resp =
-        if let Some(texture_handle) = app.downloader_state.song.cover_texture_handle.as_ref() {
+        if let Some(texture_handle) = app.cover_texture(ui.ctx()) {
             ui.add(Image::new(texture_handle.id(), image_size).sense(Sense::click()))
         } else {
             let unk_cover_resp = ui.add_sized(
                 image_size,
                 Label::new(
-                    RichText::new(app.downloader_state.song_origin.to_string())
+                    RichText::new(origin_icon(app.downloader_state.song_origin))
                         .size(iconst!(COVER_SIZE) * 0.15)
-                        .color(iconst!(INACTIVE_FG_STROKE_COLOR)),
+                        .color(ui.visuals().widgets.inactive.fg_stroke.color),
                 )
                 .sense(Sense::click()),
             );
@@ -182,13 +868,16 @@ fn draw_cover_image(app: &mut App, ui: &mut Ui) {
                 unk_cover_resp.rect,
                 Rounding::same(3.),
                 Color32::TRANSPARENT,
-                Stroke::new(1., iconst!(INACTIVE_FG_STROKE_COLOR)),
+                Stroke::new(1., ui.visuals().widgets.inactive.fg_stroke.color),
             );
             unk_cover_resp
         };
     if cover_resp.clicked() {
-        if let Some(new_cover_path) = rfd::FileDialog::new().pick_file() {
-            app.set_cover_by_path(ui.ctx(), new_cover_path)
+        if let Some(new_cover_path) = rfd::FileDialog::new()
+            .add_filter("image", &["png", "jpg", "jpeg", "webp", "bmp", "gif"])
+            .pick_file()
+        {
+            app.set_cover_by_path(new_cover_path)
         }
     }
     cover_resp.on_hover_text_at_pointer(label!("edit", EDIT_ICON));
@@ -236,7 +925,29 @@ fn draw_options(app: &mut App, ui: &mut Ui) {
                     mk_row(
                         &mut body,
                         label!("title", DETAILS_TITLE_ICON),
-                        |ui| ui.text_edit_singleline(&mut app.downloader_state.song.title),
+                        |ui| {
+                            StripBuilder::new(ui)
+                                .size(Size::remainder())
+                                .size(Size::exact(iconst!(CLEAN_BUTTON_WIDTH)))
+                                .horizontal(|mut strip| {
+                                    strip.cell(|ui| {
+                                        ui.text_edit_singleline(
+                                            &mut app.downloader_state.song.title,
+                                        );
+                                    });
+                                    strip.cell(|ui| {
+                                        if ui
+                                            .button(label!("clean", CLEAN_ICON))
+                                            .on_hover_text("strip junk like (official video)")
+                                            .clicked()
+                                        {
+                                            app.downloader_state
+                                                .song
+                                                .clean_title(&app.settings.title_junk_patterns);
+                                        }
+                                    });
+                                })
+                        },
                         None,
                     );
                     mk_row(
@@ -248,20 +959,160 @@ fn draw_options(app: &mut App, ui: &mut Ui) {
                     mk_row(
                         &mut body,
                         label!("album", DETAILS_ALBUM_ICON),
-                        |ui| ui.text_edit_singleline(&mut app.downloader_state.song.album),
-                        Some(&mut app.downloader_state.separate_album),
+                        |ui| {
+                            if ui
+                                .text_edit_singleline(&mut app.downloader_state.song.album)
+                                .changed()
+                            {
+                                app.downloader_state.song.album_from_batch = false;
+                            }
+                        },
+                        Some(&mut app.downloader_state.song.separate_album),
                     );
                     mk_row(
                         &mut body,
                         label!("album artist", DETAILS_ALBUM_ARTIST_ICON),
-                        |ui| ui.text_edit_singleline(&mut app.downloader_state.song.album_artist),
-                        Some(&mut app.downloader_state.separate_album_artist),
+                        |ui| {
+                            if ui
+                                .text_edit_singleline(&mut app.downloader_state.song.album_artist)
+                                .changed()
+                            {
+                                app.downloader_state.song.album_artist_from_batch = false;
+                            }
+                        },
+                        Some(&mut app.downloader_state.song.separate_album_artist),
                     );
                     mk_row(
                         &mut body,
                         label!("composer", DETAILS_COMPOSER_ICON),
                         |ui| ui.text_edit_singleline(&mut app.downloader_state.song.composer),
-                        Some(&mut app.downloader_state.seperate_composer),
+                        Some(&mut app.downloader_state.song.separate_composer),
+                    );
+                    mk_row(
+                        &mut body,
+                        label!("track", DETAILS_TRACK_ICON),
+                        |ui| ui.text_edit_singleline(&mut app.downloader_state.song.track),
+                        None,
+                    );
+                    mk_row(
+                        &mut body,
+                        label!("year", DETAILS_YEAR_ICON),
+                        |ui| ui.text_edit_singleline(&mut app.downloader_state.song.year),
+                        None,
+                    );
+                    mk_row(
+                        &mut body,
+                        label!("genre", DETAILS_GENRE_ICON),
+                        |ui| ui.text_edit_singleline(&mut app.downloader_state.song.genre),
+                        None,
+                    );
+                    mk_row(
+                        &mut body,
+                        label!("lyrics", DETAILS_LYRICS_ICON),
+                        |ui| {
+                            StripBuilder::new(ui)
+                                .sizes(Size::remainder(), 2)
+                                .horizontal(|mut strip| {
+                                    strip.cell(|ui| {
+                                        ui.toggle_value(
+                                            &mut app.downloader_state.editing_lyrics,
+                                            label!("edit", EDIT_ICON),
+                                        );
+                                    });
+                                    strip.cell(|ui| {
+                                        if ui.button(label!("load", FOLDER_ICON)).clicked() {
+                                            if let Some(lyrics_path) = rfd::FileDialog::new()
+                                                .add_filter("lyrics", &["lrc", "txt"])
+                                                .pick_file()
+                                            {
+                                                app.set_lyrics_by_path(lyrics_path);
+                                            }
+                                        }
+                                    });
+                                })
+                        },
+                        None,
+                    );
+                    mk_row(
+                        &mut body,
+                        label!("musicbrainz", DETAILS_MUSICBRAINZ_ICON),
+                        |ui| {
+                            StripBuilder::new(ui)
+                                .sizes(Size::remainder(), 3)
+                                .horizontal(|mut strip| {
+                                    strip.cell(|ui| {
+                                        let looking_up =
+                                            app.downloader_state.looking_up_matches.is_some();
+                                        if ui
+                                            .add_enabled(!looking_up, Button::new("lookup"))
+                                            .clicked()
+                                        {
+                                            app.lookup_matches();
+                                        }
+                                    });
+                                    strip.cell(|ui| {
+                                        let mut chosen_index = None;
+                                        ui.add_enabled_ui(
+                                            !app.downloader_state.match_candidates.is_empty(),
+                                            |ui| {
+                                                ComboBox::from_id_source("match_candidates")
+                                                    .width(ui.available_width())
+                                                    .selected_text("choose match...")
+                                                    .show_ui(ui, |ui| {
+                                                        for (index, candidate) in app
+                                                            .downloader_state
+                                                            .match_candidates
+                                                            .iter()
+                                                            .enumerate()
+                                                        {
+                                                            if ui
+                                                                .selectable_label(
+                                                                    false,
+                                                                    candidate.summary(),
+                                                                )
+                                                                .clicked()
+                                                            {
+                                                                chosen_index = Some(index);
+                                                            }
+                                                        }
+                                                    });
+                                            },
+                                        );
+                                        if let Some(chosen_index) = chosen_index {
+                                            app.apply_match(chosen_index);
+                                        }
+                                    });
+                                    strip.cell(|ui| {
+                                        ui.checkbox(
+                                            &mut app.downloader_state.overwrite_matched_tags,
+                                            "overwrite",
+                                        )
+                                        .on_hover_text("replace tags that were already filled in");
+                                    });
+                                })
+                        },
+                        None,
+                    );
+                    mk_row(
+                        &mut body,
+                        label!("audio", DETAILS_AUDIO_ICON),
+                        |ui| {
+                            let song = &app.downloader_state.song;
+                            let unknown = || String::from("-");
+                            ui.label(format!(
+                                "{} | {} | {}",
+                                song.duration().map(app::format_timestamp).unwrap_or_else(unknown),
+                                song.audio_info
+                                    .bitrate_kbps
+                                    .map(|bitrate| format!("{bitrate} kb/s"))
+                                    .unwrap_or_else(unknown),
+                                song.audio_info
+                                    .sample_rate
+                                    .map(|sample_rate| format!("{sample_rate} Hz"))
+                                    .unwrap_or_else(unknown),
+                            ))
+                        },
+                        None,
                     );
                     mk_row(
                         &mut body,
@@ -271,28 +1122,146 @@ fn draw_options(app: &mut App, ui: &mut Ui) {
                             app.downloader_state.song.volume
                         ),
                         |ui| {
+                            let offset =
+                                app::parse_volume_offset(&app.downloader_state.volume_offset);
+                            let is_typed = !app.downloader_state.volume_offset.trim().is_empty();
+                            let limit = app::VOLUME_OFFSET_LIMIT;
+                            let clamped = offset
+                                .filter(|offset| offset.abs() > limit)
+                                .map(|offset| offset.clamp(-limit, limit));
+                            let (text_color, hint) = match (offset, clamped) {
+                                (None, _) if is_typed => (
+                                    Some(ui.visuals().error_fg_color),
+                                    Some("not a number of dB".to_string()),
+                                ),
+                                (_, Some(clamped)) => (
+                                    Some(ui.visuals().warn_fg_color),
+                                    Some(format!("clamped to {clamped:+} dB")),
+                                ),
+                                _ => (None, None),
+                            };
                             StripBuilder::new(ui)
-                                .sizes(Size::remainder(), 2)
+                                .sizes(Size::remainder(), 3)
                                 .horizontal(|mut strip| {
                                     strip.cell(|ui| {
-                                        TextEdit::singleline(
+                                        let response = TextEdit::singleline(
                                             &mut app.downloader_state.volume_offset,
                                         )
                                         .hint_text("adjust volume (dB)...")
-                                        .show(ui);
+                                        .text_color_opt(text_color)
+                                        .show(ui)
+                                        .response;
+                                        if let Some(hint) = &hint {
+                                            response.on_hover_text(hint);
+                                        }
+                                    });
+                                    strip.cell(|ui| {
+                                        let apply_text = if clamped.is_some() {
+                                            "apply (clamped)"
+                                        } else {
+                                            "apply"
+                                        };
+                                        if ui
+                                            .add_enabled(offset.is_some(), Button::new(apply_text))
+                                            .clicked()
+                                        {
+                                            app.apply_volume_offset();
+                                        }
+                                    });
+                                    strip.cell(|ui| {
+                                        if ui
+                                            .button("re-measure")
+                                            .on_hover_text("measure the current audio again")
+                                            .clicked()
+                                        {
+                                            app.remeasure_volume();
+                                        }
+                                    });
+                                })
+                        },
+                        None,
+                    );
+                    mk_row(
+                        &mut body,
+                        format!(
+                            "{} ({}dB)",
+                            label!("peak", PEAK_ICON),
+                            app.downloader_state.song.peak_volume
+                        ),
+                        |ui| {
+                            StripBuilder::new(ui)
+                                .sizes(Size::remainder(), 2)
+                                .horizontal(|mut strip| {
+                                    strip.cell(|ui| {
+                                        TextEdit::singleline(&mut app.downloader_state.target_peak)
+                                            .hint_text("target peak (dB)...")
+                                            .show(ui);
                                     });
                                     strip.cell(|ui| {
                                         if ui
                                             .add_enabled(
-                                                app.downloader_state
-                                                    .volume_offset
-                                                    .parse::<f32>()
-                                                    .is_ok(),
-                                                Button::new("apply"),
+                                                !app.downloader_state.target_peak.trim().is_empty(),
+                                                Button::new("normalize to target"),
                                             )
                                             .clicked()
                                         {
-                                            app.apply_volume_offset();
+                                            app.apply_target_peak();
+                                        }
+                                    });
+                                })
+                        },
+                        None,
+                    );
+                    mk_row(
+                        &mut body,
+                        label!("loudness", LOUDNESS_ICON),
+                        |ui| match app.settings.loudness_mode {
+                            LoudnessMode::Normalize => {
+                                let normalize_label =
+                                    format!("normalize to {} LUFS", app.settings.target_lufs);
+                                if ui.button(normalize_label).clicked() {
+                                    app.normalize_loudness();
+                                }
+                            }
+                            LoudnessMode::ReplayGain => {
+                                ui.label(format!(
+                                    "track gain {:+.2} dB",
+                                    app.downloader_state.song.replaygain_track_gain()
+                                ))
+                                .on_hover_text("written as a replaygain tag on save");
+                            }
+                        },
+                        None,
+                    );
+                    mk_row(
+                        &mut body,
+                        label!("trim", TRIM_ICON),
+                        |ui| {
+                            StripBuilder::new(ui)
+                                .sizes(Size::remainder(), 3)
+                                .horizontal(|mut strip| {
+                                    strip.cell(|ui| {
+                                        TextEdit::singleline(&mut app.downloader_state.trim_start)
+                                            .hint_text("start (mm:ss)...")
+                                            .show(ui);
+                                    });
+                                    strip.cell(|ui| {
+                                        TextEdit::singleline(&mut app.downloader_state.trim_end)
+                                            .hint_text("end (mm:ss)...")
+                                            .show(ui);
+                                    });
+                                    strip.cell(|ui| {
+                                        let times_valid = [
+                                            &app.downloader_state.trim_start,
+                                            &app.downloader_state.trim_end,
+                                        ]
+                                        .into_iter()
+                                        .all(|time| app::parse_timestamp(time).is_some());
+                                        if ui
+                                            .add_enabled(times_valid, Button::new("apply"))
+                                            .clicked()
+                                        {
+                                            app.apply_trim();
                                         }
                                     });
                                 })
@@ -311,6 +1280,15 @@ fn draw_options(app: &mut App, ui: &mut Ui) {
                     app.save();
                 }
             });
+            if ui.button(label!("edit externally", EXTERNAL_EDIT_ICON)).clicked() {
+                app.edit_in_external_editor();
+            }
+            let has_saved = app.downloader_state.last_saved_path.lock().is_some();
+            ui.add_enabled_ui(has_saved, |ui| {
+                if ui.button(label!("show in folder", FOLDER_ICON)).clicked() {
+                    app.reveal_last_saved();
+                }
+            });
         });
     });
 }
@@ -318,41 +1296,61 @@ fn draw_options(app: &mut App, ui: &mut Ui) {
 fn draw_downloader(app: &mut App, ui: &mut Ui) {
     ui.vertical_centered_justified(|ui| {
         spacer(ui);
-        let tedit_response = TextEdit::singleline(&mut app.downloader_state.song.source_url)
-            .hint_text("enter query url...")
-            .horizontal_align(egui::Align::Center)
-            .show(ui)
-            .response;
+        let tedit_response = ui
+            .with_layout(Layout::right_to_left(egui::Align::Center), |ui| {
+                let paste_response = ui
+                    .add_enabled(!app.is_song_loading(), Button::new(iconst!(PASTE_ICON)))
+                    .on_hover_text("paste from clipboard");
+                if paste_response.clicked() {
+                    app.paste_query_url();
+                }
+                TextEdit::singleline(&mut app.downloader_state.song.source_url)
+                    .hint_text("enter query url...")
+                    .horizontal_align(egui::Align::Center)
+                    .desired_width(f32::INFINITY)
+                    .show(ui)
+                    .response
+            })
+            .inner;
 
         if tedit_response.changed() {
-            app::remove_characters(&mut app.downloader_state.song.source_url, &["\""]);
+            remove_characters(&mut app.downloader_state.song.source_url, &["\""]);
             if !app.is_song_loading() {
                 app.downloader_state.song_origin =
                     Origin::from_link(&app.downloader_state.song.source_url);
             }
         }
 
-        if ui.button("query").clicked() {
+        if app.is_song_loading() {
+            if ui.button(label!("cancel", CANCEL_ICON)).clicked() {
+                app.cancel();
+            }
+        } else if ui.button("query").clicked() {
             app.query(ui.ctx())
         };
 
+        if !app.downloader_state.queue.is_empty() {
+            spacer(ui);
+            draw_queue(app, ui);
+            spacer(ui);
+            draw_batch_tags(app, ui);
+        }
+
         spacer(ui);
         ui.separator();
         spacer(ui);
     });
 
-    let loading_spinner_rect = Rect::from_center_size(
-        ui.available_rect_before_wrap().center(),
-        Vec2::splat(iconst!(LOADING_SPINNER_SIZE)),
-    );
+    let loading_center = ui.available_rect_before_wrap().center();
 
-    if !app.downloader_state.separate_album {
+    // only for display, saving falls back the same way in `generate_metadata_tuples`
+    if !app.downloader_state.song.separate_album {
         app.downloader_state.song.album = app.downloader_state.song.title.clone();
     }
-    if !app.downloader_state.separate_album_artist {
+    if !app.downloader_state.song.separate_album_artist {
         app.downloader_state.song.album_artist = app.downloader_state.song.artist.clone();
     }
-    if !app.downloader_state.seperate_composer {
+    if !app.downloader_state.song.separate_composer {
         app.downloader_state.song.composer = app.downloader_state.song.artist.clone();
     }
     let controls_enabled = app.is_song_loaded() && !app.is_song_loading();
@@ -388,14 +1386,139 @@ fn draw_downloader(app: &mut App, ui: &mut Ui) {
         controls_response.on_hover_text_at_pointer("query a song first");
     }
 
+    if controls_enabled {
+        egui::Window::new(label!("lyrics", DETAILS_LYRICS_ICON))
+            .open(&mut app.downloader_state.editing_lyrics)
+            .default_size(vec2(300., 300.))
+            .show(ui.ctx(), |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    TextEdit::multiline(&mut app.downloader_state.song.lyrics)
+                        .hint_text("paste lyrics...")
+                        .desired_width(f32::INFINITY)
+                        .show(ui);
+                });
+            });
+    }
+
+    // text fields keep their keys
+    if controls_enabled && !ui.ctx().wants_keyboard_input() {
+        handle_playback_keys(app, ui);
+    }
+
     if app.is_song_loading() {
-        ui.put(
-            loading_spinner_rect,
-            Spinner::new().size(iconst!(LOADING_SPINNER_SIZE)),
-        );
+        if let Some(progress) = app.downloader_state.progress {
+            ui.put(
+                Rect::from_center_size(
+                    loading_center,
+                    vec2(iconst!(LOADING_PROGRESS_WIDTH), iconst!(LOADING_SPINNER_SIZE)),
+                ),
+                ProgressBar::new(progress).show_percentage(),
+            );
+        } else {
+            ui.put(
+                Rect::from_center_size(loading_center, Vec2::splat(iconst!(LOADING_SPINNER_SIZE))),
+                Spinner::new().size(iconst!(LOADING_SPINNER_SIZE)),
+            );
+        }
+    }
+}
+
+fn handle_playback_keys(app: &mut App, ui: &mut Ui) {
+    let (toggle, seek_back, seek_forward) = ui.input(|i| {
+        (
+            i.key_pressed(Key::Space),
+            i.key_pressed(Key::ArrowLeft),
+            i.key_pressed(Key::ArrowRight),
+        )
+    });
+    if toggle {
+        let _ = app.toggle_song_playback();
+    }
+    if seek_back {
+        let _ = app.seek_song_by(-iconst!(KEYBOARD_SEEK_SECS));
+    }
+    if seek_forward {
+        let _ = app.seek_song_by(iconst!(KEYBOARD_SEEK_SECS));
+    }
+}
+
+fn draw_queue(app: &mut App, ui: &mut Ui) {
+    let queue_index = app.downloader_state.queue_index;
+    let queue_len = app.downloader_state.queue.len();
+    let entry_title = |index: usize| {
+        if index == queue_index && !app.downloader_state.song.title.is_empty() {
+            &app.downloader_state.song.title
+        } else {
+            &app.downloader_state.queue[index].title
+        }
+    };
+
+    let mut selected_index = queue_index;
+    // finished entries can be browsed while the rest of the playlist downloads
+    ui.add_enabled_ui(app.downloader_state.loading_song.is_none(), |ui| {
+        ComboBox::from_id_source("queue")
+            .width(ui.available_width())
+            .selected_text(label!(
+                format!("{}/{queue_len}  {}", queue_index + 1, entry_title(queue_index)),
+                QUEUE_ICON
+            ))
+            .show_ui(ui, |ui| {
+                for index in 0..queue_len {
+                    let status = &app.downloader_state.queue[index].status;
+                    let status_icon = match status {
+                        QueueStatus::Queued => iconst!(QUEUED_ICON),
+                        QueueStatus::Downloading => iconst!(DOWNLOADING_ICON),
+                        QueueStatus::Done => iconst!(DONE_ICON),
+                        QueueStatus::Failed(_) => iconst!(FAILED_ICON),
+                    };
+                    let response = ui.add_enabled_ui(*status == QueueStatus::Done, |ui| {
+                        ui.selectable_value(
+                            &mut selected_index,
+                            index,
+                            format!("{status_icon} {}. {}", index + 1, entry_title(index)),
+                        )
+                    });
+                    if let QueueStatus::Failed(error) = status {
+                        response.response.on_disabled_hover_text(error.as_str());
+                    }
+                }
+            });
+    });
+    if selected_index != queue_index {
+        app.select_queue_entry(selected_index);
     }
 }
 
+fn draw_batch_tags(app: &mut App, ui: &mut Ui) {
+    ui.collapsing(label!("apply to all", BATCH_TAGS_ICON), |ui| {
+        egui::Grid::new("batch_tags").num_columns(2).show(ui, |ui| {
+            let batch_tags = &mut app.downloader_state.batch_tags;
+            let field = |ui: &mut Ui, label: String, value: &mut String| {
+                ui.label(label);
+                ui.text_edit_singleline(value);
+                ui.end_row();
+            };
+            field(ui, label!("album", DETAILS_ALBUM_ICON), &mut batch_tags.album);
+            field(
+                ui,
+                label!("album artist", DETAILS_ALBUM_ARTIST_ICON),
+                &mut batch_tags.album_artist,
+            );
+            field(ui, label!("genre", DETAILS_GENRE_ICON), &mut batch_tags.genre);
+            field(ui, label!("year", DETAILS_YEAR_ICON), &mut batch_tags.year);
+        });
+        ui.horizontal(|ui| {
+            ui.checkbox(
+                &mut app.downloader_state.batch_tags.overwrite,
+                "overwrite separately set tags",
+            );
+            if ui.button("apply").clicked() {
+                app.apply_to_queue();
+            }
+        });
+    });
+}
+
 fn pathbuf_to_string(path: &PathBuf) -> String {
     path.as_path().to_string_lossy().to_string()
 }
@@ -432,8 +1555,63 @@ pub fn draw_root(app: &mut App, ctx: &Context) {
 
     CentralPanel::default().show(ctx, |ui| match app.current_page {
         InterfacePage::Downloader => draw_downloader(app, ui),
+        InterfacePage::History => draw_history(app, ui),
         InterfacePage::Settings => draw_settings(app, ui),
     });
+
+    if app.confirming_quit {
+        draw_quit_confirmation(app, ctx);
+    }
+    if app.saved_session.is_some() {
+        draw_session_prompt(app, ctx);
+    }
+}
+
+fn draw_session_prompt(app: &mut App, ctx: &Context) {
+    let Some(session) = app.saved_session.as_ref() else {
+        return;
+    };
+    let description = if session.title.is_empty() {
+        session.source_url.clone()
+    } else {
+        session.title.clone()
+    };
+    egui::Window::new(label!("restore session", HISTORY_ICON))
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label(format!("pick up where you left off with \"{description}\"?"));
+            ui.horizontal(|ui| {
+                if ui.button("restore").clicked() {
+                    app.restore_session(ui.ctx());
+                }
+                if ui.button("discard").clicked() {
+                    app.discard_session();
+                }
+            });
+        });
+}
+
+fn draw_quit_confirmation(app: &mut App, ctx: &Context) {
+    egui::Window::new(label!("quit", QUIT_ICON))
+        .collapsible(false)
+        .resizable(false)
+        .anchor(Align2::CENTER_CENTER, Vec2::ZERO)
+        .show(ctx, |ui| {
+            ui.label("a download is in progress, quit anyway?");
+            ui.horizontal(|ui| {
+                if ui.button("quit").clicked() {
+                    app.confirm_quit();
+                }
+                if ui.button("quit when done").clicked() {
+                    app.wait_then_quit();
+                }
+                if ui.button("stay").clicked() {
+                    app.confirming_quit = false;
+                }
+            });
+        });
 }
 
 fn draw_waveform(app: &mut App, ui: &mut Ui) -> Response {
@@ -452,70 +1630,162 @@ fn draw_waveform(app: &mut App, ui: &mut Ui) -> Response {
     };
 
     let icon_size = 16.;
+    let icon_padding = 8.;
+    let has_queue = !app.downloader_state.queue.is_empty();
+    let mut action_icon_pos = widget_response.rect.left_center() + vec2(icon_padding, 0.);
+
+    let prev_response = has_queue.then(|| {
+        let response = draw_icon_button(
+            ui,
+            action_icon_pos,
+            Align2::LEFT_CENTER,
+            iconst!(PREVIOUS_ICON),
+            icon_size,
+        );
+        action_icon_pos = response.rect.right_center() + vec2(icon_padding, 0.);
+        response
+    });
 
-    let mut icon_font_id = FontId::default();
-    icon_font_id.size = icon_size;
+    let audio_available = app.audio_manager.is_some();
+    let icon_response = ui
+        .add_enabled_ui(audio_available, |ui| {
+            draw_icon_button(ui, action_icon_pos, Align2::LEFT_CENTER, action_icon, icon_size)
+        })
+        .inner
+        .on_disabled_hover_text("no audio output, retry from the settings");
+
+    let next_response = has_queue.then(|| {
+        draw_icon_button(
+            ui,
+            icon_response.rect.right_center() + vec2(icon_padding, 0.),
+            Align2::LEFT_CENTER,
+            iconst!(NEXT_ICON),
+            icon_size,
+        )
+    });
 
-    let icon_padding = 8.;
-    let icon_color = iconst!(INACTIVE_FG_STROKE_COLOR);
-    let action_icon_pos = widget_response.rect.left_center() + vec2(icon_padding, 0.);
-
-    let icon_rect = ui.painter().text(
-        action_icon_pos,
-        Align2::LEFT_CENTER,
-        action_icon,
-        icon_font_id,
-        icon_color,
+    let channel_icon_pos = widget_response.rect.right_center() - vec2(icon_padding, 0.);
+    let channel_response = draw_icon_button(
+        ui,
+        channel_icon_pos,
+        Align2::RIGHT_CENTER,
+        &app.downloader_state.preview_channel.to_string(),
+        icon_size * 0.75,
+    );
+
+    let palette = app.settings.theme.palette();
+    let repeat_icon_pos = channel_response.rect.left_center() - vec2(icon_padding, 0.);
+    let repeat_response = draw_colored_icon_button(
+        ui,
+        repeat_icon_pos,
+        Align2::RIGHT_CENTER,
+        iconst!(REPEAT_ICON),
+        icon_size,
+        if app.downloader_state.repeat {
+            palette.accent
+        } else {
+            palette.inactive_fg_stroke
+        },
     );
 
-    let icon_response = ui.allocate_rect(icon_rect, Sense::click());
+    let mute_icon_pos = repeat_response.rect.left_center() - vec2(icon_padding, 0.);
+    let mute_response = ui
+        .add_enabled_ui(audio_available, |ui| {
+            draw_icon_button(
+                ui,
+                mute_icon_pos,
+                Align2::RIGHT_CENTER,
+                if app.downloader_state.muted {
+                    iconst!(MUTED_ICON)
+                } else {
+                    iconst!(VOLUME_ICON)
+                },
+                icon_size,
+            )
+        })
+        .inner;
+
+    let elapsed = app
+        .downloader_state
+        .song_handle
+        .as_ref()
+        .map(|h| h.position() as f32)
+        .unwrap_or_default();
+    let duration = app.downloader_state.song.duration().unwrap_or_default();
+    let time_pos = mute_response.rect.left_center() - vec2(icon_padding, 0.);
+    // monospace so the bars don't shift as the digits change
+    let time_rect = ui.painter().text(
+        time_pos,
+        Align2::RIGHT_CENTER,
+        format!(
+            "{} / {}",
+            app::format_timestamp(elapsed.min(duration)),
+            app::format_timestamp(duration)
+        ),
+        FontId::monospace(icon_size * 0.75),
+        palette.inactive_fg_stroke,
+    );
 
     let mut audio_rect = widget_response.rect;
 
     audio_rect.set_top(audio_rect.top() + icon_padding / 2.);
     audio_rect.set_bottom(audio_rect.bottom() - icon_padding / 2.);
-    audio_rect.set_left(icon_size * 2. + icon_padding);
-    audio_rect.set_right(widget_response.rect.right() - icon_padding);
+    audio_rect.set_left(match next_response.as_ref() {
+        Some(next_response) => next_response.rect.right() + icon_padding,
+        None => icon_size * 2. + icon_padding,
+    });
+    audio_rect.set_right(time_rect.left() - icon_padding);
 
     let waveform_response = ui.allocate_rect(audio_rect, Sense::click_and_drag());
 
-    let hover_ratio = (app
+    let seekable = app
         .downloader_state
         .song_handle
         .as_ref()
         .is_some_and(|h| h.state() != kira::sound::PlaybackState::Stopped)
-        && ui.is_enabled())
-    .then_some(ui.ctx().pointer_hover_pos().and_then(|p| {
-        audio_rect.contains(p).then_some(
-            ((p.x - audio_rect.left()) / audio_rect.width())
-                .min(1.)
-                .max(0.),
+        && ui.is_enabled();
+    let ratio_at = |p: Pos2| {
+        ((p.x - audio_rect.left()) / audio_rect.width())
+            .min(1.)
+            .max(0.)
+    };
+    let hover_ratio = seekable
+        .then_some(
+            ui.ctx()
+                .pointer_hover_pos()
+                .and_then(|p| audio_rect.contains(p).then_some(ratio_at(p))),
         )
-    }))
-    .flatten();
+        .flatten();
 
     let bar_paddding = 2.;
     let total_width = audio_rect.width();
-    let bar_width = ((total_width - (WAVEFORM_LENGTH as f32 - 1.) * bar_paddding)
-        / WAVEFORM_LENGTH as f32)
-        .trunc();
+    let bar_count = ((total_width + bar_paddding)
+        / (iconst!(WAVEFORM_BAR_WIDTH) + bar_paddding))
+        .max(1.) as usize;
+    let bar_width = ((total_width - (bar_count as f32 - 1.) * bar_paddding) / bar_count as f32)
+        .trunc()
+        .max(1.);
 
     let mut next_bar_offset = audio_rect.left();
-    let playback_position = app.song_position_ratio().unwrap_or_default();
+    // the playhead follows the pointer while scrubbing, even when paused
+    let playback_position = match app.downloader_state.scrub_ratio {
+        Some(scrub_ratio) => scrub_ratio,
+        None => app.song_position_ratio().unwrap_or_default(),
+    };
     let painter = ui.painter();
 
     ui.ctx().tessellation_options_mut(|t| t.feathering = false);
 
-    let empty_color = iconst!(WAVEFORM_EMPTY_COLOR);
-    let filled_color = iconst!(WAVEFORM_FILLED_COLOR);
+    let empty_color = palette.waveform_empty;
+    let filled_color = palette.accent;
 
     let delta_weak_color = mix_colors(empty_color, filled_color, 0.2);
     let delta_strong_color = mix_colors(empty_color, filled_color, 0.5);
 
     app.downloader_state
         .song
-        .waveform
-        .0
+        .waveform(app.settings.waveform_mode)
+        .resampled(bar_count)
         .iter()
         .enumerate()
         .for_each(|(i, s)| {
@@ -523,8 +1793,8 @@ fn draw_waveform(app: &mut App, ui: &mut Ui) -> Response {
                 pos2(next_bar_offset, audio_rect.top()),
                 pos2(next_bar_offset + bar_width, audio_rect.bottom()),
             );
-            let previous_bar_position = i as f32 / WAVEFORM_LENGTH as f32;
-            let bar_position = (i + 1) as f32 / WAVEFORM_LENGTH as f32;
+            let previous_bar_position = i as f32 / bar_count as f32;
+            let bar_position = (i + 1) as f32 / bar_count as f32;
 
             let gamma = ((playback_position - previous_bar_position)
                 / (bar_position - previous_bar_position))
@@ -556,45 +1826,187 @@ fn draw_waveform(app: &mut App, ui: &mut Ui) -> Response {
             let _ = app.seek_song(hover_ratio);
         }
     }
+    if seekable && waveform_response.dragged() {
+        if let Some(pointer_pos) = waveform_response.interact_pointer_pos() {
+            let _ = app.scrub_song(ratio_at(pointer_pos));
+        }
+    }
+    if waveform_response.drag_released() {
+        if let Some(scrub_ratio) = app.downloader_state.scrub_ratio.take() {
+            let _ = app.seek_song(scrub_ratio);
+        }
+    }
+    if let (Some(hover_ratio), Some(duration)) = (hover_ratio, app.downloader_state.song.duration())
+    {
+        waveform_response.on_hover_text_at_pointer(app::format_timestamp(hover_ratio * duration));
+    }
 
     if icon_response.clicked() {
         let _ = app.toggle_song_playback();
     }
+    // switching entries swaps the song out, which would pull it from under a save
+    let can_switch = app.downloader_state.loading_song.is_none();
+    if prev_response.is_some_and(|r| r.on_hover_text("previous").clicked()) && can_switch {
+        app.prev_song();
+    }
+    if next_response.is_some_and(|r| r.on_hover_text("next").clicked()) && can_switch {
+        app.next_song();
+    }
+    if channel_response.on_hover_text("preview channel").clicked() {
+        let next_channel = app.downloader_state.preview_channel.next();
+        let _ = app.set_preview_channel(next_channel);
+    }
+    if repeat_response.on_hover_text("repeat").clicked() {
+        let repeat = !app.downloader_state.repeat;
+        let _ = app.set_repeat(repeat);
+    }
+    if mute_response.on_hover_text("mute").clicked() {
+        let muted = !app.downloader_state.muted;
+        let _ = app.set_muted(muted);
+    }
     widget_response
 }
 
-pub fn load_style(ctx: &Context) {
+fn draw_icon_button(ui: &mut Ui, pos: Pos2, align: Align2, icon: &str, size: f32) -> Response {
+    let color = ui.visuals().widgets.inactive.fg_stroke.color;
+    draw_colored_icon_button(ui, pos, align, icon, size, color)
+}
+
+fn draw_colored_icon_button(
+    ui: &mut Ui,
+    pos: Pos2,
+    align: Align2,
+    icon: &str,
+    size: f32,
+    color: Color32,
+) -> Response {
+    let mut icon_font_id = FontId::default();
+    icon_font_id.size = size;
+
+    let icon_rect = ui.painter().text(pos, align, icon, icon_font_id, color);
+    ui.allocate_rect(icon_rect, Sense::click())
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum ThemeMode {
+    #[default]
+    Dark,
+    Light,
+}
+
+impl Display for ThemeMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Dark => write!(f, "dark"),
+            Self::Light => write!(f, "light"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub mode: ThemeMode,
+    pub accent: [u8; 3],
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        let accent = iconst!(ACCENT_COLOR);
+        Self {
+            mode: ThemeMode::default(),
+            accent: [accent.r(), accent.g(), accent.b()],
+        }
+    }
+}
+
+pub struct Palette {
+    pub primary_bg_fill: Color32,
+    pub secondary_bg_fill: Color32,
+    pub inactive_fg_stroke: Color32,
+    pub inactive_bg_fill: Color32,
+    pub hovered_bg_fill: Color32,
+    pub hovered_bg_stroke: Color32,
+    pub hovered_fg_stroke: Color32,
+    pub active_bg_fill: Color32,
+    pub selected_bg_fill: Color32,
+    pub waveform_empty: Color32,
+    pub accent: Color32,
+}
+
+impl Theme {
+    pub fn accent_color(&self) -> Color32 {
+        let [r, g, b] = self.accent;
+        Color32::from_rgb(r, g, b)
+    }
+    pub fn palette(&self) -> Palette {
+        match self.mode {
+            ThemeMode::Dark => Palette {
+                primary_bg_fill: iconst!(PRIMARY_BG_FILL_COLOR),
+                secondary_bg_fill: iconst!(SECONDARY_BG_FILL_COLOR),
+                inactive_fg_stroke: iconst!(INACTIVE_FG_STROKE_COLOR),
+                inactive_bg_fill: iconst!(INACTIVE_BG_FILL_COLOR),
+                hovered_bg_fill: iconst!(HOVERED_BG_FILL_COLOR),
+                hovered_bg_stroke: iconst!(HOVERED_BG_STROKE_COLOR),
+                hovered_fg_stroke: iconst!(HOVERED_FG_STROKE_COLOR),
+                active_bg_fill: iconst!(ACTIVE_BG_FILL_COLOR),
+                selected_bg_fill: iconst!(SELECTED_BG_FILL_COLOR),
+                waveform_empty: iconst!(WAVEFORM_EMPTY_COLOR),
+                accent: self.accent_color(),
+            },
+            ThemeMode::Light => Palette {
+                primary_bg_fill: iconst!(LIGHT_PRIMARY_BG_FILL_COLOR),
+                secondary_bg_fill: iconst!(LIGHT_SECONDARY_BG_FILL_COLOR),
+                inactive_fg_stroke: iconst!(LIGHT_INACTIVE_FG_STROKE_COLOR),
+                inactive_bg_fill: iconst!(LIGHT_INACTIVE_BG_FILL_COLOR),
+                hovered_bg_fill: iconst!(LIGHT_HOVERED_BG_FILL_COLOR),
+                hovered_bg_stroke: iconst!(LIGHT_HOVERED_BG_STROKE_COLOR),
+                hovered_fg_stroke: iconst!(LIGHT_HOVERED_FG_STROKE_COLOR),
+                active_bg_fill: iconst!(LIGHT_ACTIVE_BG_FILL_COLOR),
+                selected_bg_fill: iconst!(LIGHT_SELECTED_BG_FILL_COLOR),
+                waveform_empty: iconst!(LIGHT_WAVEFORM_EMPTY_COLOR),
+                accent: self.accent_color(),
+            },
+        }
+    }
+}
+
+pub fn load_style(ctx: &Context, theme: &Theme) {
     let mut style = Style::default();
     fn stroke(color: Color32) -> Stroke {
         Stroke::new(1., color)
     }
+    let palette = theme.palette();
+    if theme.mode == ThemeMode::Light {
+        style.visuals = Visuals::light();
+    }
+
     style.visuals.widgets.noninteractive.bg_stroke =
-        stroke(scale_color(iconst!(PRIMARY_BG_FILL_COLOR), 1.5));
-    style.visuals.widgets.noninteractive.bg_fill = iconst!(PRIMARY_BG_FILL_COLOR);
-    style.visuals.window_fill = iconst!(PRIMARY_BG_FILL_COLOR);
-    style.visuals.panel_fill = iconst!(PRIMARY_BG_FILL_COLOR);
+        stroke(scale_color(palette.primary_bg_fill, 1.5));
+    style.visuals.widgets.noninteractive.bg_fill = palette.primary_bg_fill;
+    style.visuals.window_fill = palette.primary_bg_fill;
+    style.visuals.panel_fill = palette.primary_bg_fill;
 
-    style.visuals.extreme_bg_color = iconst!(SECONDARY_BG_FILL_COLOR);
+    style.visuals.extreme_bg_color = palette.secondary_bg_fill;
 
-    style.visuals.widgets.inactive.bg_fill = iconst!(INACTIVE_BG_FILL_COLOR);
-    style.visuals.widgets.inactive.weak_bg_fill = iconst!(INACTIVE_BG_FILL_COLOR);
+    style.visuals.widgets.inactive.bg_fill = palette.inactive_bg_fill;
+    style.visuals.widgets.inactive.weak_bg_fill = palette.inactive_bg_fill;
 
-    style.visuals.widgets.inactive.fg_stroke = stroke(iconst!(INACTIVE_FG_STROKE_COLOR));
+    style.visuals.widgets.inactive.fg_stroke = stroke(palette.inactive_fg_stroke);
 
-    style.visuals.widgets.hovered.bg_fill = iconst!(HOVERED_BG_FILL_COLOR);
-    style.visuals.widgets.hovered.weak_bg_fill = iconst!(HOVERED_BG_FILL_COLOR);
+    style.visuals.widgets.hovered.bg_fill = palette.hovered_bg_fill;
+    style.visuals.widgets.hovered.weak_bg_fill = palette.hovered_bg_fill;
 
-    style.visuals.widgets.hovered.bg_stroke = stroke(iconst!(HOVERED_BG_STROKE_COLOR));
-    style.visuals.widgets.hovered.fg_stroke = stroke(iconst!(HOVERED_FG_STROKE_COLOR));
+    style.visuals.widgets.hovered.bg_stroke = stroke(palette.hovered_bg_stroke);
+    style.visuals.widgets.hovered.fg_stroke = stroke(palette.hovered_fg_stroke);
 
-    style.visuals.widgets.active.bg_fill = iconst!(ACTIVE_BG_FILL_COLOR);
-    style.visuals.widgets.active.weak_bg_fill = iconst!(ACTIVE_BG_FILL_COLOR);
+    style.visuals.widgets.active.bg_fill = palette.active_bg_fill;
+    style.visuals.widgets.active.weak_bg_fill = palette.active_bg_fill;
 
-    style.visuals.widgets.active.bg_stroke = stroke(iconst!(ACTIVE_BG_STROKE_COLOR));
-    style.visuals.widgets.active.fg_stroke = stroke(iconst!(ACTIVE_FG_STROKE_COLOR));
+    style.visuals.widgets.active.bg_stroke = stroke(palette.accent);
+    style.visuals.widgets.active.fg_stroke = stroke(palette.accent);
 
-    style.visuals.selection.stroke = stroke(iconst!(SELECTED_FG_STROKE_COLOR));
-    style.visuals.selection.bg_fill = iconst!(SELECTED_BG_FILL_COLOR);
+    style.visuals.selection.stroke = stroke(palette.accent);
+    style.visuals.selection.bg_fill = palette.selected_bg_fill;
 
     Stroke::default();
 
@@ -660,14 +2072,34 @@ pub mod constants {
 
     pub const DOWNLOADER_ICON: &str = "📥";
     pub const SETTINGS_ICON: &str = "⛭";
+    pub const CANCEL_ICON: &str = egui_phosphor::X_CIRCLE;
+    pub const LOUDNESS_ICON: &str = egui_phosphor::WAVEFORM;
+    pub const TRIM_ICON: &str = egui_phosphor::SCISSORS;
+    pub const REPEAT_ICON: &str = egui_phosphor::REPEAT;
+    pub const PASTE_ICON: &str = egui_phosphor::CLIPBOARD_TEXT;
+    pub const QUEUE_ICON: &str = egui_phosphor::LIST_NUMBERS;
+    pub const BATCH_TAGS_ICON: &str = egui_phosphor::STACK;
+    pub const HISTORY_ICON: &str = egui_phosphor::CLOCK_COUNTER_CLOCKWISE;
+    pub const EXPORT_ICON: &str = egui_phosphor::EXPORT;
+    pub const IMPORT_ICON: &str = egui_phosphor::DOWNLOAD_SIMPLE;
+    pub const DONE_ICON: &str = egui_phosphor::CHECK;
+    pub const QUEUED_ICON: &str = egui_phosphor::HOURGLASS;
+    pub const DOWNLOADING_ICON: &str = egui_phosphor::DOWNLOAD_SIMPLE;
+    pub const FAILED_ICON: &str = egui_phosphor::X;
     pub const PLAY_ICON: &str = "▶";
+    pub const PREVIOUS_ICON: &str = egui_phosphor::SKIP_BACK;
+    pub const NEXT_ICON: &str = egui_phosphor::SKIP_FORWARD;
     pub const PAUSE_ICON: &str = "⏸";
     pub const STOP_ICON: &str = "⏹";
     pub const EDIT_ICON: &str = egui_phosphor::PEN;
+    pub const EXTERNAL_EDIT_ICON: &str = egui_phosphor::ARROW_SQUARE_OUT;
     pub const YOUTUBE_ICON: &str = egui_phosphor::YOUTUBE_LOGO;
     pub const SOUNDCLOUD_ICON: &str = egui_phosphor::SOUNDCLOUD_LOGO;
+    pub const BANDCAMP_ICON: &str = egui_phosphor::VINYL_RECORD;
+    pub const WEB_ICON: &str = egui_phosphor::GLOBE;
     pub const FOLDER_ICON: &str = egui_phosphor::FOLDER;
     pub const VOLUME_ICON: &str = egui_phosphor::SPEAKER_SIMPLE_HIGH;
+    pub const MUTED_ICON: &str = egui_phosphor::SPEAKER_SIMPLE_X;
 
     pub const SPACER_SIZE: f32 = 5.;
     pub const DETAILS_ROW_HEIGHT: f32 = 20.;
@@ -675,14 +2107,28 @@ pub mod constants {
     pub const COVER_SIZE: f32 = 256.;
     pub const COVER_PADDING: f32 = 10.;
     pub const LOADING_SPINNER_SIZE: f32 = 15.;
+    pub const LOADING_PROGRESS_WIDTH: f32 = 200.;
 
     pub const SONG_BAR_HEIGHT: f32 = 35.;
+    pub const WAVEFORM_BAR_WIDTH: f32 = 1.;
+    pub const CLEAN_BUTTON_WIDTH: f32 = 70.;
+    pub const HISTORY_TABLE_HEIGHT: f32 = 120.;
+    pub const KEYBOARD_SEEK_SECS: f64 = 5.;
 
     pub const DETAILS_TITLE_ICON: &str = egui_phosphor::TEXT_T;
     pub const DETAILS_ARTIST_ICON: &str = egui_phosphor::USER;
     pub const DETAILS_ALBUM_ICON: &str = egui_phosphor::IMAGES_SQUARE;
     pub const DETAILS_ALBUM_ARTIST_ICON: &str = egui_phosphor::USER_PLUS;
     pub const DETAILS_COMPOSER_ICON: &str = egui_phosphor::USER_GEAR;
+    pub const DETAILS_TRACK_ICON: &str = egui_phosphor::HASH;
+    pub const DETAILS_YEAR_ICON: &str = egui_phosphor::CALENDAR;
+    pub const DETAILS_GENRE_ICON: &str = egui_phosphor::MUSIC_NOTES;
+    pub const DETAILS_AUDIO_ICON: &str = egui_phosphor::INFO;
+    pub const DETAILS_MUSICBRAINZ_ICON: &str = egui_phosphor::MAGNIFYING_GLASS;
+    pub const CLEAN_ICON: &str = egui_phosphor::MAGIC_WAND;
+    pub const PEAK_ICON: &str = egui_phosphor::CHART_LINE_UP;
+    pub const QUIT_ICON: &str = egui_phosphor::SIGN_OUT;
+    pub const DETAILS_LYRICS_ICON: &str = egui_phosphor::MICROPHONE;
 
     pub const WINDOW_SIZE: Vec2 = vec2(750., 375. + SONG_BAR_HEIGHT);
 
@@ -695,12 +2141,21 @@ pub mod constants {
     pub const HOVERED_BG_STROKE_COLOR: Color32 = Color32::from_rgb(103, 110, 149);
     pub const HOVERED_FG_STROKE_COLOR: Color32 = Color32::from_rgb(166, 172, 205);
     pub const ACTIVE_BG_FILL_COLOR: Color32 = Color32::from_rgb(33, 37, 50);
-    pub const ACTIVE_BG_STROKE_COLOR: Color32 = Color32::from_rgb(128, 203, 196);
-    pub const ACTIVE_FG_STROKE_COLOR: Color32 = Color32::from_rgb(128, 203, 196);
-    pub const SELECTED_FG_STROKE_COLOR: Color32 = Color32::from_rgb(128, 203, 196);
     pub const SELECTED_BG_FILL_COLOR: Color32 = Color32::from_rgb(28, 31, 43);
     pub const ACCENT_COLOR: Color32 = Color32::from_rgb(128, 203, 196);
 
+    pub const ERROR_COLOR: Color32 = Color32::from_rgb(240, 113, 120);
+
     pub const WAVEFORM_EMPTY_COLOR: Color32 = Color32::from_rgb(90, 100, 120);
-    pub const WAVEFORM_FILLED_COLOR: Color32 = ACCENT_COLOR;
+
+    pub const LIGHT_PRIMARY_BG_FILL_COLOR: Color32 = Color32::from_rgb(238, 240, 246);
+    pub const LIGHT_SECONDARY_BG_FILL_COLOR: Color32 = Color32::from_rgb(252, 252, 254);
+    pub const LIGHT_INACTIVE_FG_STROKE_COLOR: Color32 = Color32::from_rgb(96, 102, 130);
+    pub const LIGHT_INACTIVE_BG_FILL_COLOR: Color32 = Color32::from_rgb(222, 226, 236);
+    pub const LIGHT_HOVERED_BG_FILL_COLOR: Color32 = Color32::from_rgb(230, 233, 242);
+    pub const LIGHT_HOVERED_BG_STROKE_COLOR: Color32 = Color32::from_rgb(140, 146, 172);
+    pub const LIGHT_HOVERED_FG_STROKE_COLOR: Color32 = Color32::from_rgb(40, 44, 62);
+    pub const LIGHT_ACTIVE_BG_FILL_COLOR: Color32 = Color32::from_rgb(230, 233, 242);
+    pub const LIGHT_SELECTED_BG_FILL_COLOR: Color32 = Color32::from_rgb(212, 230, 228);
+    pub const LIGHT_WAVEFORM_EMPTY_COLOR: Color32 = Color32::from_rgb(184, 190, 206);
 }
@@ -0,0 +1,5 @@
+pub mod cli;
+pub mod command;
+pub mod musicbrainz;
+pub mod pipeline;
+pub mod song;
@@ -1,11 +1,43 @@
-#![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
+#![cfg_attr(all(windows, not(debug_assertions)), windows_subsystem = "windows")]
 // hide console window on Windows in release
 
 mod app;
-mod command;
 mod interface;
-mod song;
+
+use songdl::cli;
 
 fn main() {
-    app::init()
+    let args = std::env::args().skip(1).collect::<Vec<_>>();
+    if args.is_empty() {
+        return app::init();
+    }
+    #[cfg(all(windows, not(debug_assertions)))]
+    attach_console();
+    let run = |args| {
+        let settings = app::init_settings()?;
+        settings.set_commands();
+        cli::run(args, settings.pipeline())
+    };
+    if let Err(error) = cli::CliArgs::parse(args).and_then(run) {
+        eprintln!("{error}");
+        std::process::exit(1);
+    }
+}
+
+/// release builds on windows start without a console, so the cli borrows the one it was run
+/// from, or opens its own
+#[cfg(all(windows, not(debug_assertions)))]
+fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+        fn AllocConsole() -> i32;
+    }
+    // neither takes pointers, failing only leaves the process without a console
+    unsafe {
+        if AttachConsole(ATTACH_PARENT_PROCESS) == 0 {
+            AllocConsole();
+        }
+    }
 }
@@ -0,0 +1,149 @@
+use std::{
+    sync::OnceLock,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use parking_lot::Mutex;
+use serde_json::Value;
+
+use crate::command::{check_cancelled, http_get};
+
+const MUSICBRAINZ_API_URL: &str = "https://musicbrainz.org/ws/2";
+const COVER_ART_ARCHIVE_URL: &str = "https://coverartarchive.org";
+const LOOKUP_LIMIT: usize = 10;
+/// musicbrainz allows a client one request per second
+const REQUEST_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default, Clone)]
+pub struct MatchCandidate {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub year: String,
+    pub track: String,
+    pub release_id: String,
+}
+
+impl MatchCandidate {
+    pub fn summary(&self) -> String {
+        let mut summary = format!("{} - {}", self.artist, self.title);
+        if !self.album.is_empty() {
+            summary.push_str(&format!(" ({}", self.album));
+            if !self.year.is_empty() {
+                summary.push_str(&format!(", {}", self.year));
+            }
+            summary.push(')');
+        }
+        summary
+    }
+}
+
+/// blocks until a request can be made without going over `REQUEST_INTERVAL`
+fn wait_for_rate_limit() {
+    static LAST_REQUEST: OnceLock<Mutex<Option<Instant>>> = OnceLock::new();
+    let mut last_request = LAST_REQUEST.get_or_init(|| Mutex::new(None)).lock();
+    if let Some(elapsed) = last_request.map(|last_request| last_request.elapsed()) {
+        if elapsed < REQUEST_INTERVAL {
+            thread::sleep(REQUEST_INTERVAL - elapsed);
+        }
+    }
+    *last_request = Some(Instant::now());
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
+
+/// quotes `value` as a lucene phrase
+fn lucene_phrase(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn read_str(json: &Value, field: &str) -> String {
+    json.get(field)
+        .and_then(|value| value.as_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// recordings matching `artist` and `title`, one candidate per recording's first release
+pub fn lookup(artist: &str, title: &str, proxy_url: Option<&str>) -> Result<Vec<MatchCandidate>> {
+    check_cancelled()?;
+    let query = format!(
+        "recording:{} AND artist:{}",
+        lucene_phrase(title),
+        lucene_phrase(artist)
+    );
+    let url = format!(
+        "{MUSICBRAINZ_API_URL}/recording?query={}&limit={LOOKUP_LIMIT}&fmt=json",
+        percent_encode(&query)
+    );
+    wait_for_rate_limit();
+    let response: Value = serde_json::from_slice(&http_get(&url, proxy_url)?)?;
+    let recordings = response
+        .get("recordings")
+        .and_then(|recordings| recordings.as_array())
+        .context("unexpected musicbrainz response")?;
+
+    Ok(recordings
+        .iter()
+        .map(|recording| {
+            let artist = recording
+                .get("artist-credit")
+                .and_then(|credits| credits.as_array())
+                .map(|credits| {
+                    credits
+                        .iter()
+                        .map(|credit| read_str(credit, "name") + &read_str(credit, "joinphrase"))
+                        .collect::<String>()
+                })
+                .unwrap_or_default();
+            let release = recording
+                .get("releases")
+                .and_then(|releases| releases.get(0))
+                .cloned()
+                .unwrap_or_default();
+            // vinyl and the like number tracks "A1", "B2", so those fall back to the position
+            let number = release
+                .pointer("/media/0/track/0/number")
+                .and_then(|number| number.as_str())
+                .unwrap_or_default();
+            let track = match number.parse::<u32>() {
+                Ok(_) => number.to_string(),
+                Err(_) => release
+                    .pointer("/media/0/track-offset")
+                    .and_then(|offset| offset.as_u64())
+                    .map(|offset| (offset + 1).to_string())
+                    .unwrap_or_default(),
+            };
+            MatchCandidate {
+                title: read_str(recording, "title"),
+                artist,
+                album: read_str(&release, "title"),
+                year: read_str(&release, "date").chars().take(4).collect(),
+                track,
+                release_id: read_str(&release, "id"),
+            }
+        })
+        .collect())
+}
+
+/// the release's front cover, empty if it has none
+pub fn cover(release_id: &str, proxy_url: Option<&str>) -> Result<Vec<u8>> {
+    check_cancelled()?;
+    if release_id.is_empty() {
+        return Ok(vec![]);
+    }
+    let url = format!("{COVER_ART_ARCHIVE_URL}/release/{release_id}/front-500");
+    Ok(http_get(&url, proxy_url).unwrap_or_default())
+}
@@ -0,0 +1,310 @@
+use std::{fs, io::Cursor, path::PathBuf, time::Duration};
+
+use anyhow::{bail, Result};
+use image::DynamicImage;
+use serde_json::Value;
+
+use crate::{
+    command::{
+        convert_audio, download_audio, download_thumbnail, extract_metadata, extract_thumbnail,
+        is_transient_error, sleep_cancellable, EncodeOptions, YtdlOptions,
+    },
+    song::{FolderStructure, Origin, Song},
+};
+
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// what the loading and saving steps report their progress through, so they can run headless
+pub trait Status {
+    fn caption(&self, caption: &str) -> Result<()>;
+    /// `None` is indeterminate progress
+    fn progress(&self, progress: Option<f32>);
+    /// retries `f` while it fails with a transient error, waiting twice as long each time
+    fn retry<T>(&self, max_attempts: u32, mut f: impl FnMut() -> Result<T>) -> Result<T> {
+        let mut attempt = 1;
+        loop {
+            match f() {
+                Err(error) if attempt < max_attempts && is_transient_error(&error) => {
+                    let backoff = RETRY_BACKOFF * 2_u32.pow(attempt - 1);
+                    attempt += 1;
+                    self.caption(&format!("retrying ({attempt}/{max_attempts})..."))?;
+                    sleep_cancellable(backoff)?;
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+/// reports nowhere
+impl Status for () {
+    fn caption(&self, _caption: &str) -> Result<()> {
+        Ok(())
+    }
+    fn progress(&self, _progress: Option<f32>) {}
+}
+
+/// the download, convert, tag and save steps without any of the gui
+#[derive(Default, Clone)]
+pub struct SongPipeline {
+    pub encode_options: EncodeOptions,
+    pub ytdl_options: YtdlOptions,
+    pub cache_waveform_peaks: bool,
+    /// the patterns to clean loaded titles with, `None` leaves them as they are
+    pub title_cleanup: Option<Vec<String>>,
+    pub folder_structure: FolderStructure,
+    pub write_replaygain: bool,
+    /// tag the encoder and the encoding date
+    pub write_provenance: bool,
+}
+
+impl SongPipeline {
+    /// loads `query_url` as a local file or through yt-dl, depending on what it looks like
+    pub fn load(&self, query_url: String, status: &impl Status) -> Result<Song> {
+        if Origin::from_link(&query_url) == Origin::Local {
+            self.load_local(query_url, status)
+        } else {
+            self.load_remote(query_url, status)
+        }
+    }
+
+    pub fn load_local(&self, query_url: String, status: &impl Status) -> Result<Song> {
+        let mut song = Song::default();
+
+        status.caption("reading...")?;
+        let audio_bytes = fs::read(&query_url)?;
+
+        if audio_bytes.is_empty() {
+            bail!("read error")
+        }
+
+        status.caption("converting audio...")?;
+        status.progress(None);
+        let (converted_audio_bytes, encode_options) =
+            convert_audio(&audio_bytes, &self.encode_options)?;
+
+        if converted_audio_bytes.is_empty() {
+            bail!("audio conversion error")
+        }
+
+        status.caption("extracting thumbnail...")?;
+        let cover_bytes = extract_thumbnail(&audio_bytes)?;
+
+        status.caption("parsing metadata...")?;
+        let audio_details = extract_metadata(&audio_bytes)?;
+        song.update_metadata_from_json(audio_details);
+        if let Some(junk_patterns) = &self.title_cleanup {
+            song.clean_title(junk_patterns);
+        }
+
+        song.cover_bytes = cover_bytes.into();
+        song.audio_bytes = converted_audio_bytes.into();
+        song.encode_options = encode_options;
+
+        let mut waveform_ready = false;
+        if self.cache_waveform_peaks {
+            if !song.load_cached_waveform(&query_url, &audio_bytes) {
+                status.caption("reading song...")?;
+                song.update_audio_frames()?;
+                let _ = song.cache_waveform(&query_url, &audio_bytes);
+            }
+            waveform_ready = true;
+        }
+        song.source_url = query_url;
+
+        if !waveform_ready {
+            status.caption("reading song...")?;
+            song.update_audio_frames()?;
+        }
+        song.update_current_volume()?;
+        song.update_audio_info()?;
+        Ok(song)
+    }
+
+    pub fn load_remote(&self, query_url: String, status: &impl Status) -> Result<Song> {
+        let mut song = Song::default();
+        let ytdl_options = &self.ytdl_options;
+
+        status.caption("downloading audio...")?;
+        status.progress(Some(0.));
+        let (audio_bytes, audio_details) = status.retry(ytdl_options.max_attempts, || {
+            download_audio(&query_url, ytdl_options, |progress| status.progress(Some(progress)))
+        })?;
+
+        if audio_bytes.is_empty() {
+            bail!("download error")
+        }
+
+        status.caption("converting audio...")?;
+        status.progress(None);
+        let (converted_audio_bytes, encode_options) =
+            convert_audio(&audio_bytes, &self.encode_options)?;
+
+        if converted_audio_bytes.is_empty() {
+            bail!("audio conversion error")
+        }
+
+        status.caption("downloading thumbnail...")?;
+        let thumbnail_url = best_thumbnail_url(&audio_details);
+        let image_bytes = if thumbnail_url.is_empty() {
+            vec![]
+        } else {
+            match status.retry(ytdl_options.max_attempts, || {
+                download_thumbnail(&thumbnail_url, ytdl_options.proxy_url.as_deref())
+            }) {
+                // still unreachable after retrying, the song is usable without a cover
+                Err(error) if is_transient_error(&error) => vec![],
+                result => result?,
+            }
+        };
+
+        status.caption("parsing metadata...")?;
+        song.update_metadata_from_json(audio_details);
+        if let Some(junk_patterns) = &self.title_cleanup {
+            song.clean_title(junk_patterns);
+        }
+
+        // the largest thumbnails are often webp, which gets re-encoded to jpeg at full resolution
+        status.caption("loading cover...")?;
+        set_cover_from_image(&mut song, &image_bytes)?;
+
+        song.audio_bytes = converted_audio_bytes.into();
+        song.encode_options = encode_options;
+        song.source_url = query_url;
+
+        status.caption("reading song...")?;
+        song.update_audio_frames()?;
+        song.update_current_volume()?;
+        song.update_audio_info()?;
+        Ok(song)
+    }
+
+    /// saves `song` under `save_path`, returning where it ended up
+    pub fn save(
+        &self,
+        song: &mut Song,
+        save_path: &PathBuf,
+        status: &impl Status,
+    ) -> Result<PathBuf> {
+        if !song.cover_bytes.is_empty() && !song.encode_options.format.supports_cover() {
+            status.caption(&format!(
+                "{} can't embed a cover, skipping it...",
+                song.encode_options.format
+            ))?;
+        }
+        status.caption("updating song metadata...")?;
+        song.update_bytes_from_metadata(self.write_replaygain, self.write_provenance)?;
+        status.caption("writing song to disk...")?;
+        song.write_to_disk(save_path, self.folder_structure)
+    }
+}
+
+/// an empty `image_bytes` leaves the cover as it was
+pub fn set_cover_from_image(song: &mut Song, image_bytes: &[u8]) -> Result<()> {
+    if image_bytes.is_empty() {
+        return Ok(());
+    }
+    let image = image::load_from_memory(image_bytes)?;
+
+    // jpeg has no alpha channel, so pngs and the like are flattened first
+    let mut cover_bytes = vec![];
+    DynamicImage::ImageRgb8(image.to_rgb8())
+        .write_to(&mut Cursor::new(&mut cover_bytes), image::ImageFormat::Jpeg)?;
+
+    song.cover_bytes = cover_bytes.into();
+    Ok(())
+}
+
+/// the largest of yt-dl's `thumbnails`, falling back to its default `thumbnail`,
+/// empty when there's neither
+fn best_thumbnail_url(details: &Value) -> String {
+    let url = |json: &Value, field: &str| {
+        json.get(field).and_then(Value::as_str).unwrap_or_default().to_string()
+    };
+    details
+        .get("thumbnails")
+        .and_then(|thumbnails| thumbnails.as_array())
+        .and_then(|thumbnails| {
+            // yt-dl sorts thumbnails from worst to best, so later ones win ties
+            thumbnails
+                .iter()
+                .enumerate()
+                .filter(|(_, thumbnail)| !url(thumbnail, "url").is_empty())
+                .max_by_key(|(index, thumbnail)| {
+                    let dimension = |field| thumbnail.get(field).and_then(Value::as_u64);
+                    let area = dimension("width").unwrap_or(0) * dimension("height").unwrap_or(0);
+                    (area, *index)
+                })
+                .map(|(_, thumbnail)| url(thumbnail, "url"))
+        })
+        .unwrap_or_else(|| url(details, "thumbnail"))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{
+        command::{
+            tempfile_with_suffix,
+            tests::{ffmpeg_available, sine_audio},
+            AudioFormat,
+        },
+        song::WAVEFORM_RESOLUTION,
+    };
+
+    #[test]
+    fn load_local_and_save_a_wav() {
+        if !ffmpeg_available() {
+            return;
+        }
+        let (_wav, wav_path) = tempfile_with_suffix(&sine_audio("wav"), ".wav").unwrap();
+        let pipeline = SongPipeline::default();
+
+        let mut song = pipeline.load_local(wav_path.clone(), &()).unwrap();
+        assert!(!song.audio_bytes.is_empty());
+        assert_eq!(song.source_url, wav_path);
+        assert_eq!(song.waveform.0.len(), WAVEFORM_RESOLUTION);
+        let duration = song.duration().unwrap();
+        assert!((duration - 1.).abs() < 0.1, "{duration}");
+
+        song.title = String::from("sine");
+        let save_dir = tempfile::tempdir().unwrap();
+        let saved_path = pipeline.save(&mut song, &save_dir.path().to_path_buf(), &()).unwrap();
+        assert!(saved_path.starts_with(save_dir.path()));
+        assert_eq!(extract_metadata(&fs::read(saved_path).unwrap()).unwrap()["title"], "sine");
+    }
+
+    #[test]
+    fn load_local_keeps_a_wav_as_is() {
+        if !ffmpeg_available() {
+            return;
+        }
+        let (_wav, wav_path) = tempfile_with_suffix(&sine_audio("wav"), ".wav").unwrap();
+        let pipeline = SongPipeline {
+            encode_options: EncodeOptions { keep_original: true, ..Default::default() },
+            ..Default::default()
+        };
+
+        let song = pipeline.load_local(wav_path, &()).unwrap();
+        assert!(song.encode_options.format == AudioFormat::Wav);
+        assert!(song.audio_bytes.starts_with(b"RIFF"));
+    }
+
+    #[test]
+    fn best_thumbnail_prefers_the_largest() {
+        let details = json!({
+            "thumbnail": "default",
+            "thumbnails": [
+                { "url": "small", "width": 120, "height": 90 },
+                { "url": "large", "width": 1280, "height": 720 },
+                { "url": null, "width": 1920, "height": 1080 },
+                { "url": "unsized" },
+            ],
+        });
+        assert_eq!(best_thumbnail_url(&details), "large");
+        assert_eq!(best_thumbnail_url(&json!({ "thumbnail": "default" })), "default");
+        assert_eq!(best_thumbnail_url(&json!({ "thumbnail": null })), "");
+    }
+}
@@ -1,23 +1,48 @@
-use std::{fmt::Display, io::Cursor, path::PathBuf};
+use std::{
+    fmt::Display,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
 
-use anyhow::Result;
-use egui::TextureHandle;
-use kira::sound::static_sound::{StaticSoundData, StaticSoundSettings};
+use anyhow::{bail, Result};
+use chrono::Local;
+use regex::{Captures, Regex};
+use kira::{
+    dsp::Frame,
+    sound::static_sound::{StaticSoundData, StaticSoundSettings},
+};
+use parking_lot::Mutex;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 
-use crate::{
-    app::{self, json_read},
-    command::{
-        apply_volume_offset, get_average_volume, write_cover_to_audio, write_metadata_to_audio,
-        FFMPEG_AUDIO_FORMAT_EXT,
-    },
-    iconst,
+use crate::command::{
+    apply_volume_offset, decode_to_pcm, get_volume_levels, json_read, normalize_loudness,
+    probe_audio_info, trim_audio, write_cover_to_audio, write_metadata_to_audio, AudioInfo,
+    EncodeOptions, PCM_PREVIEW_SAMPLE_RATE,
 };
 
+/// matched case-insensitively as whole words against the contents of bracketed title groups
+pub const DEFAULT_TITLE_JUNK_PATTERNS: [&str; 10] = [
+    "official",
+    "video",
+    "audio",
+    "lyric",
+    "lyrics",
+    "visualizer",
+    "hd",
+    "4k",
+    "feat",
+    "ft",
+];
+
 #[derive(Default, Clone, Copy, PartialEq)]
 pub enum Origin {
     YouTube,
     Soundcloud,
+    Bandcamp,
+    /// any other http(s) link, left for yt-dl to figure out
+    Web,
     Local,
 
     #[default]
@@ -29,6 +54,7 @@ impl Origin {
         match self {
             Self::YouTube => "youtube.",
             Self::Soundcloud => "soundcloud.",
+            Self::Bandcamp => "bandcamp.",
             _ => "",
         }
     }
@@ -40,6 +66,10 @@ impl Origin {
             Origin::YouTube
         } else if contains_origin(Origin::Soundcloud) {
             Origin::Soundcloud
+        } else if contains_origin(Origin::Bandcamp) {
+            Origin::Bandcamp
+        } else if link.starts_with("http://") || link.starts_with("https://") {
+            Origin::Web
         } else if PathBuf::from(link).exists() {
             Origin::Local
         } else {
@@ -48,17 +78,117 @@ impl Origin {
     }
 }
 
-impl Display for Origin {
+/// playlists, sets, and albums are downloaded entry by entry into the queue
+pub fn is_playlist_link(link: &str) -> bool {
+    ["/playlist", "/sets/", "/album/"]
+        .into_iter()
+        .any(|component| link.contains(component))
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum FolderStructure {
+    #[default]
+    Flat,
+    Artist,
+    ArtistAlbum,
+}
+
+impl FolderStructure {
+    pub const ALL: [FolderStructure; 3] = [Self::Flat, Self::Artist, Self::ArtistAlbum];
+}
+
+impl Display for FolderStructure {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            Self::YouTube => write!(f, "{}", iconst!(YOUTUBE_ICON)),
-            Self::Soundcloud => write!(f, "{}", iconst!(SOUNDCLOUD_ICON)),
-            Self::Local => write!(f, "{}", iconst!(FOLDER_ICON)),
-            _ => write!(f, "?"),
+            Self::Flat => write!(f, "flat"),
+            Self::Artist => write!(f, "artist"),
+            Self::ArtistAlbum => write!(f, "artist/album"),
         }
     }
 }
 
+/// how non-square covers are shown
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum CoverFit {
+    #[default]
+    Crop,
+    Pad,
+}
+
+impl CoverFit {
+    pub const ALL: [CoverFit; 2] = [Self::Crop, Self::Pad];
+}
+
+impl Display for CoverFit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Crop => write!(f, "crop to square"),
+            Self::Pad => write!(f, "pad to square"),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum WaveformMode {
+    #[default]
+    Peak,
+    Rms,
+}
+
+impl WaveformMode {
+    pub const ALL: [WaveformMode; 2] = [Self::Peak, Self::Rms];
+}
+
+impl Display for WaveformMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Peak => write!(f, "peak"),
+            Self::Rms => write!(f, "rms"),
+        }
+    }
+}
+
+/// normalizing rewrites the audio, replaygain only tags it for players to adjust
+#[derive(Serialize, Deserialize, Default, Clone, Copy, PartialEq)]
+pub enum LoudnessMode {
+    #[default]
+    Normalize,
+    ReplayGain,
+}
+
+impl LoudnessMode {
+    pub const ALL: [LoudnessMode; 2] = [Self::Normalize, Self::ReplayGain];
+}
+
+impl Display for LoudnessMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Normalize => write!(f, "normalize"),
+            Self::ReplayGain => write!(f, "replaygain tags"),
+        }
+    }
+}
+
+/// the mean volume replaygain tags bring tracks to, in dB
+pub const REPLAYGAIN_REFERENCE: f32 = -18.;
+
+pub const AUDIO_EXTENSIONS: [&str; 10] =
+    ["mp3", "flac", "wav", "ogg", "opus", "m4a", "aac", "webm", "aiff", "wma"];
+
+pub fn is_audio_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+            AUDIO_EXTENSIONS.contains(&extension.to_ascii_lowercase().as_str())
+        })
+}
+
+pub fn remove_characters(s: &mut String, c: &[&str]) {
+    c.into_iter().for_each(|ss| {
+        *s = s.replace(ss, "");
+    });
+}
+
 #[derive(Default, Clone)]
 pub struct Song {
     pub title: String,
@@ -66,31 +196,165 @@ pub struct Song {
     pub album: String,
     pub album_artist: String,
     pub composer: String,
+    pub track: String,
+    pub year: String,
+    pub genre: String,
+    pub lyrics: String,
+    /// unset fields mirror the title or artist instead of being edited on their own
+    pub separate_album: bool,
+    pub separate_album_artist: bool,
+    pub separate_composer: bool,
+    /// set by a queue-wide apply rather than by hand, so the next one may replace them
+    pub album_from_batch: bool,
+    pub album_artist_from_batch: bool,
 
-    pub audio_bytes: Vec<u8>,
-    pub cover_bytes: Vec<u8>,
+    /// shared, so cloning a song for a background op doesn't copy the audio
+    pub audio_bytes: Arc<[u8]>,
+    pub cover_bytes: Arc<[u8]>,
 
     pub source_url: String,
     pub volume: f32,
+    pub peak_volume: f32,
+    pub encode_options: EncodeOptions,
+    pub audio_info: AudioInfo,
 
-    pub cover_texture_handle: Option<TextureHandle>,
     pub audio_frames: Option<StaticSoundData>,
     pub waveform: Waveform,
+    pub rms_waveform: Waveform,
 }
 
-pub const WAVEFORM_LENGTH: usize = 230;
-#[derive(Clone)]
-pub struct Waveform(pub [f32; WAVEFORM_LENGTH]);
-
-impl Default for Waveform {
-    fn default() -> Self {
-        Self([0.; WAVEFORM_LENGTH])
-    }
+/// what saved songs are tagged as encoded by
+pub fn encoded_by() -> String {
+    format!("{} {}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
 }
 
+/// how many peaks are kept per song, `Waveform::resampled` brings them down to the bar count
+pub const WAVEFORM_RESOLUTION: usize = 1024;
+#[derive(Default, Clone)]
+pub struct Waveform(pub Vec<f32>);
+
 impl Waveform {
     pub fn new(values: Vec<f32>) -> Self {
-        Self(values.try_into().unwrap_or([0.; WAVEFORM_LENGTH]))
+        Self(values)
+    }
+    /// scales the values so the tallest one is 1
+    fn normalized(mut self) -> Self {
+        let max = self.0.iter().cloned().fold(0., f32::max);
+        if max > 0. {
+            self.0.iter_mut().for_each(|s| *s /= max);
+        }
+        self
+    }
+    /// the peak of each of `length` evenly sized buckets
+    pub fn resampled(&self, length: usize) -> Vec<f32> {
+        if self.0.is_empty() {
+            return vec![0.; length];
+        }
+        let value_count = self.0.len();
+        (0..length)
+            .map(|bar| {
+                let start = bar * value_count / length;
+                let end = ((bar + 1) * value_count / length)
+                    .max(start + 1)
+                    .min(value_count);
+                self.0[start.min(end - 1)..end]
+                    .iter()
+                    .cloned()
+                    .fold(0., f32::max)
+            })
+            .collect()
+    }
+}
+
+/// the normalized peak and rms waveforms of `frames`, always `WAVEFORM_RESOLUTION` long
+fn waveforms_from_frames(frames: &[Frame]) -> (Waveform, Waveform) {
+    let mono_frames = frames
+        .iter()
+        .map(|f| (f.left as f32 + f.right as f32) * 0.5)
+        .collect::<Vec<_>>();
+    // bucket bounds are taken by ratio so there are always WAVEFORM_RESOLUTION values,
+    // clips shorter than that repeat frames across buckets
+    let frame_count = mono_frames.len();
+    let buckets = (0..WAVEFORM_RESOLUTION)
+        .map(|bucket| {
+            let start = bucket * frame_count / WAVEFORM_RESOLUTION;
+            let end = ((bucket + 1) * frame_count / WAVEFORM_RESOLUTION)
+                .max(start + 1)
+                .min(frame_count);
+            &mono_frames[start.min(end)..end]
+        })
+        .collect::<Vec<_>>();
+    let peaks = buckets
+        .iter()
+        .map(|bucket| bucket.iter().cloned().fold(0., f32::max))
+        .collect::<Vec<_>>();
+    let rms = buckets
+        .iter()
+        .map(|bucket| {
+            if bucket.is_empty() {
+                0.
+            } else {
+                (bucket.iter().map(|s| s * s).sum::<f32>() / bucket.len() as f32).sqrt()
+            }
+        })
+        .collect::<Vec<_>>();
+    (
+        Waveform::new(peaks).normalized(),
+        Waveform::new(rms).normalized(),
+    )
+}
+
+pub const PEAKS_SIDECAR_EXT: &str = ".peaks";
+
+#[derive(Serialize, Deserialize)]
+struct PeaksSidecar {
+    checksum: String,
+    peaks: Vec<f32>,
+    #[serde(default)]
+    rms: Vec<f32>,
+}
+
+fn peaks_sidecar_path(source_path: &str) -> PathBuf {
+    PathBuf::from(format!("{source_path}{PEAKS_SIDECAR_EXT}"))
+}
+
+/// fnv-1a, only used to detect that the source changed since the peaks were cached
+fn checksum(bytes: &[u8]) -> String {
+    let hash = bytes.iter().fold(0xcbf29ce484222325_u64, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    });
+    format!("{hash:016x}")
+}
+
+/// `N` or `N/M`, with both positive
+fn is_track_number(track: &str) -> bool {
+    let positive = |number: &str| number.parse::<u32>().is_ok_and(|number| number > 0);
+    match track.split_once('/') {
+        Some((number, total)) => positive(number) && positive(total),
+        None => positive(track),
+    }
+}
+
+/// matches any of `junk_patterns` as a whole word, only rebuilt when the patterns change
+fn junk_regex(junk_patterns: &[String]) -> Option<Regex> {
+    type JunkRegexCache = Mutex<Option<(Vec<String>, Option<Regex>)>>;
+    static CACHE: OnceLock<JunkRegexCache> = OnceLock::new();
+    let mut cache = CACHE.get_or_init(Default::default).lock();
+    match &*cache {
+        Some((patterns, junk_regex)) if patterns == junk_patterns => junk_regex.clone(),
+        _ => {
+            let words = junk_patterns
+                .iter()
+                .map(|pattern| pattern.trim())
+                .filter(|pattern| !pattern.is_empty())
+                .map(regex::escape)
+                .collect::<Vec<_>>();
+            let junk_regex = (!words.is_empty())
+                .then(|| Regex::new(&format!(r"(?i)\b({})\b", words.join("|"))).ok())
+                .flatten();
+            *cache = Some((junk_patterns.to_vec(), junk_regex.clone()));
+            junk_regex
+        }
     }
 }
 
@@ -99,49 +363,223 @@ impl Song {
         self.title = self.title.trim().to_string();
         self.artist = self.artist.trim().to_string();
         self.album = self.album.trim().to_string();
+        self.album_artist = self.album_artist.trim().to_string();
+        self.composer = self.composer.trim().to_string();
+        self.track = self.track.trim().to_string();
+        self.year = self.year.trim().to_string();
+        self.genre = self.genre.trim().to_string();
+        self.lyrics = self.lyrics.trim().to_string();
     }
-    fn generate_metadata_tuples(&mut self) -> Vec<(String, String)> {
+    fn generate_metadata_tuples(
+        &mut self,
+        write_replaygain: bool,
+        write_provenance: bool,
+    ) -> Result<Vec<(String, String)>> {
         self.trim();
-        vec![
+        if !self.track.is_empty() && !is_track_number(&self.track) {
+            bail!("track number must be a positive integer, optionally followed by /total")
+        }
+        // unless set separately, these follow the title or the artist
+        let mirrored = |separate: bool, value: &str, fallback: &str| {
+            if separate { value } else { fallback }.to_string()
+        };
+        let mut metadata = vec![
             (String::from("title"), self.title.clone()),
             (String::from("artist"), self.artist.clone()),
-            (String::from("album"), self.album.clone()),
-        ]
+            (String::from("album"), mirrored(self.separate_album, &self.album, &self.title)),
+            (
+                String::from("album_artist"),
+                mirrored(self.separate_album_artist, &self.album_artist, &self.artist),
+            ),
+            (
+                String::from("composer"),
+                mirrored(self.separate_composer, &self.composer, &self.artist),
+            ),
+            (String::from("track"), self.track.clone()),
+            (String::from("date"), self.year.clone()),
+            (String::from("genre"), self.genre.clone()),
+            // passed as a single argument, so newlines need no escaping
+            (String::from("lyrics"), self.lyrics.clone()),
+        ];
+        if write_replaygain {
+            metadata.extend([
+                (
+                    String::from("replaygain_track_gain"),
+                    format!("{:.2} dB", self.replaygain_track_gain()),
+                ),
+                (
+                    String::from("replaygain_track_peak"),
+                    format!("{:.6}", 10_f32.powf(self.peak_volume / 20.)),
+                ),
+            ]);
+        }
+        // `date` is the release year, so the encoding date goes in the comment instead,
+        // and ffmpeg replaces `encoder` with its own, so the tool goes in `encoded_by`
+        if write_provenance {
+            metadata.extend([
+                (
+                    String::from("comment"),
+                    format!("encoded by {} on {}", encoded_by(), Local::now().format("%Y-%m-%d")),
+                ),
+                (String::from("encoded_by"), encoded_by()),
+            ]);
+        }
+        Ok(metadata)
+    }
+    pub fn replaygain_track_gain(&self) -> f32 {
+        REPLAYGAIN_REFERENCE - self.volume
     }
     pub fn update_current_volume(&mut self) -> Result<()> {
-        self.volume = get_average_volume(&self.audio_bytes)?;
+        (self.volume, self.peak_volume) = get_volume_levels(&self.audio_bytes)?;
         Ok(())
     }
+    pub fn update_audio_info(&mut self) -> Result<()> {
+        self.audio_info = probe_audio_info(&self.audio_bytes)?;
+        Ok(())
+    }
+    /// prefers the decoded frames, the probed duration covers songs with cached peaks
+    pub fn duration(&self) -> Option<f32> {
+        self.audio_frames
+            .as_ref()
+            .map(|audio_frames| audio_frames.duration().as_secs_f32())
+            .or(self.audio_info.duration)
+    }
     pub fn apply_volume_offset(&mut self, offset: f32) -> Result<()> {
-        self.audio_bytes = apply_volume_offset(&self.audio_bytes, offset)?;
+        self.audio_bytes =
+            apply_volume_offset(&self.audio_bytes, offset, &self.encode_options)?.into();
+        self.update_current_volume()?;
+        self.update_audio_info()?;
+        self.update_audio_frames()?;
+        Ok(())
+    }
+    pub fn normalize_loudness(&mut self, target_lufs: f32) -> Result<()> {
+        self.audio_bytes =
+            normalize_loudness(&self.audio_bytes, target_lufs, &self.encode_options)?.into();
         self.update_current_volume()?;
+        self.update_audio_info()?;
+        self.update_audio_frames()?;
+        Ok(())
+    }
+    /// keeps only `start..end` (in seconds) of the audio
+    pub fn apply_trim(&mut self, start: f32, end: f32) -> Result<()> {
+        self.ensure_audio_frames()?;
+        let duration = self.duration().unwrap_or_default();
+        if start < 0. || end <= start {
+            bail!("trim end must be after its start")
+        }
+        if end > duration {
+            bail!("trim end is past the end of the song ({duration:.1}s)")
+        }
+        self.audio_bytes = trim_audio(&self.audio_bytes, start, end, &self.encode_options)?.into();
+        self.update_current_volume()?;
+        self.update_audio_info()?;
         self.update_audio_frames()?;
         Ok(())
     }
     pub fn update_audio_frames(&mut self) -> Result<()> {
-        let f_max = |f: &[f32]| f.iter().cloned().fold(f32::NAN, f32::max);
-
-        let audio_frames = StaticSoundData::from_cursor(
+        let audio_frames = self.decode_audio_frames()?;
+        (self.waveform, self.rms_waveform) = waveforms_from_frames(&audio_frames.frames);
+        self.audio_frames = Some(audio_frames);
+        Ok(())
+    }
+    /// songs whose peaks were cached before rms was stored fall back to the peaks
+    pub fn waveform(&self, mode: WaveformMode) -> &Waveform {
+        match mode {
+            WaveformMode::Rms if !self.rms_waveform.0.is_empty() => &self.rms_waveform,
+            _ => &self.waveform,
+        }
+    }
+    /// decodes the preview frames if a cached waveform let the query skip them
+    pub fn ensure_audio_frames(&mut self) -> Result<()> {
+        if self.audio_frames.is_none() {
+            self.audio_frames = Some(self.decode_audio_frames()?);
+        }
+        Ok(())
+    }
+    fn decode_audio_frames(&self) -> Result<StaticSoundData> {
+        match StaticSoundData::from_cursor(
             Cursor::new(self.audio_bytes.clone()),
             StaticSoundSettings::default(),
+        ) {
+            Ok(audio_frames) => Ok(audio_frames),
+            Err(_) => self.decode_audio_frames_with_ffmpeg(),
+        }
+    }
+    /// reads the sidecar peaks for `source_path`, returns false if missing or stale
+    pub fn load_cached_waveform(&mut self, source_path: &str, source_bytes: &[u8]) -> bool {
+        let cached = std::fs::read(peaks_sidecar_path(source_path))
+            .ok()
+            .and_then(|b| serde_json::from_slice::<PeaksSidecar>(&b).ok());
+        match cached {
+            Some(cached)
+                if cached.checksum == checksum(source_bytes) && !cached.peaks.is_empty() =>
+            {
+                self.waveform = Waveform::new(cached.peaks);
+                self.rms_waveform = Waveform::new(cached.rms);
+                true
+            }
+            _ => false,
+        }
+    }
+    pub fn cache_waveform(&self, source_path: &str, source_bytes: &[u8]) -> Result<()> {
+        let sidecar = PeaksSidecar {
+            checksum: checksum(source_bytes),
+            peaks: self.waveform.0.clone(),
+            rms: self.rms_waveform.0.clone(),
+        };
+        std::fs::write(
+            peaks_sidecar_path(source_path),
+            serde_json::to_vec(&sidecar)?,
         )?;
-
-        let mono_frames = audio_frames
-            .frames
-            .iter()
-            .map(|f| (f.left as f32 + f.right as f32) * 0.5)
-            .collect::<Vec<_>>();
-        let num_chunks = mono_frames.len() / WAVEFORM_LENGTH;
-        let mut waveform = mono_frames
-            .chunks_exact(num_chunks)
-            .map(|c| f_max(c))
+        Ok(())
+    }
+    fn decode_audio_frames_with_ffmpeg(&self) -> Result<StaticSoundData> {
+        let frames = decode_to_pcm(&self.audio_bytes)?
+            .chunks_exact(2)
+            .map(|s| Frame::new(s[0], s[1]))
             .collect::<Vec<_>>();
-        let max = f_max(&waveform);
-        waveform.iter_mut().for_each(|s: &mut f32| *s = *s / max);
+        Ok(StaticSoundData {
+            sample_rate: PCM_PREVIEW_SAMPLE_RATE,
+            frames: Arc::from(frames),
+            settings: StaticSoundSettings::default(),
+        })
+    }
+    /// drops bracketed groups containing one of `junk_patterns` and trailing features,
+    /// splitting `artist - title` when there's no artist yet
+    pub fn clean_title(&mut self, junk_patterns: &[String]) {
+        static BRACKET_REGEX: OnceLock<Regex> = OnceLock::new();
+        static FEATURE_REGEX: OnceLock<Regex> = OnceLock::new();
+        let bracket_regex = BRACKET_REGEX
+            .get_or_init(|| Regex::new(r"\s*(\(([^()]*)\)|\[([^\[\]]*)\])").unwrap());
+        let feature_regex = FEATURE_REGEX
+            .get_or_init(|| Regex::new(r"(?i)\s+(feat\.?|ft\.|featuring)\s.*$").unwrap());
+        let junk_regex = junk_regex(junk_patterns);
 
-        self.audio_frames = Some(audio_frames);
-        self.waveform = Waveform::new(waveform);
-        Ok(())
+        let title = bracket_regex.replace_all(&self.title, |captures: &Captures| {
+            let contents = captures
+                .get(2)
+                .or_else(|| captures.get(3))
+                .map_or("", |contents| contents.as_str());
+            if junk_regex.as_ref().is_some_and(|junk_regex| junk_regex.is_match(contents)) {
+                String::new()
+            } else {
+                captures[0].to_string()
+            }
+        });
+        let mut title = title.split_whitespace().collect::<Vec<_>>().join(" ");
+
+        if self.artist.trim().is_empty() {
+            if let Some((artist, rest)) = title.split_once(" - ") {
+                self.artist = artist.trim().to_string();
+                title = rest.trim().to_string();
+            }
+        }
+
+        let title = feature_regex.replace(&title, "");
+        let title = title.trim_matches(|c: char| c.is_whitespace() || c == '-' || c == '|');
+        if !title.is_empty() {
+            self.title = title.to_string();
+        }
     }
     pub fn update_metadata_from_json(&mut self, json: Value) {
         if let serde_json::Value::Object(mut json) = json {
@@ -163,7 +601,7 @@ impl Song {
 
             let set_if_exists = |struct_field: &mut String, json_field: &str| {
                 let value = json_read(&json, json_field);
-                if !value.is_empty() {
+                if !value.is_empty() && value != "null" {
                     *struct_field = value;
                 }
             };
@@ -171,27 +609,191 @@ impl Song {
             set_if_exists(&mut self.title, "title");
             set_if_exists(&mut self.artist, "artist");
             set_if_exists(&mut self.artist, "uploader");
+            set_if_exists(&mut self.genre, "genre");
+            set_if_exists(&mut self.lyrics, "lyrics");
+            // id3 lyrics are read back with their language, like `lyrics-eng`
+            if let Value::Object(fields) = &json {
+                if let Some(lyrics_field) = fields.keys().find(|key| key.starts_with("lyrics-")) {
+                    set_if_exists(&mut self.lyrics, lyrics_field);
+                }
+            }
+            set_if_exists(&mut self.track, "track_number");
+
+            // yt-dl's upload date is YYYYMMDD, local files carry a full date
+            set_if_exists(&mut self.year, "upload_date");
+            set_if_exists(&mut self.year, "date");
+            set_if_exists(&mut self.year, "release_year");
+            self.year = self.year.chars().take(4).collect();
         }
     }
-    pub fn write_to_disk(&self, save_path: &PathBuf) -> Result<()> {
-        let mut filename = format!("{}_{}{}", self.title, self.artist, FFMPEG_AUDIO_FORMAT_EXT)
+    /// returns the path the song was written to
+    pub fn write_to_disk(
+        &self,
+        save_path: &PathBuf,
+        folder_structure: FolderStructure,
+    ) -> Result<PathBuf> {
+        let extension = self.encode_options.format.extension();
+        let mut filename = format!("{}_{}{}", self.title, self.artist, extension)
             .to_ascii_lowercase()
             .replace(" ", "_");
 
-        app::remove_characters(&mut filename, &["/", "*", ":", "?", "\"", "<", ">", "|"]);
+        remove_characters(&mut filename, &["/", "*", ":", "?", "\"", "<", ">", "|"]);
 
         let mut final_save_path = save_path.clone();
 
+        let folder = |name: &str, fallback: &str| {
+            let mut folder = name.trim().to_string();
+            remove_characters(&mut folder, &["/", "\\", "*", ":", "?", "\"", "<", ">", "|"]);
+            // trailing dots and spaces aren't allowed in windows folder names
+            let folder = folder.trim_end_matches(['.', ' ']).to_string();
+            if folder.is_empty() {
+                fallback.to_string()
+            } else {
+                folder
+            }
+        };
+        let artist_folder = folder(&self.artist, "Unknown Artist");
+        match folder_structure {
+            FolderStructure::Flat => (),
+            FolderStructure::Artist => final_save_path.push(artist_folder),
+            FolderStructure::ArtistAlbum => {
+                final_save_path.push(artist_folder);
+                final_save_path.push(folder(&self.album, "Unknown Album"));
+            }
+        }
+        std::fs::create_dir_all(&final_save_path)?;
+
         final_save_path.push(filename);
-        std::fs::write(final_save_path, &self.audio_bytes)?;
-        Ok(())
+        std::fs::write(&final_save_path, &self.audio_bytes)?;
+        Ok(final_save_path)
     }
-    pub fn update_bytes_from_metadata(&mut self) -> Result<()> {
-        let metadata = self.generate_metadata_tuples();
-        let audio_bytes_with_metadata = write_metadata_to_audio(&self.audio_bytes, metadata)?;
+    pub fn update_bytes_from_metadata(
+        &mut self,
+        write_replaygain: bool,
+        write_provenance: bool,
+    ) -> Result<()> {
+        let metadata = self.generate_metadata_tuples(write_replaygain, write_provenance)?;
+        let format = self.encode_options.format;
+        let audio_bytes_with_metadata =
+            write_metadata_to_audio(&self.audio_bytes, metadata, format)?;
         let audio_bytes_with_cover =
-            write_cover_to_audio(&audio_bytes_with_metadata, &self.cover_bytes)?;
-        self.audio_bytes = audio_bytes_with_cover;
+            write_cover_to_audio(&audio_bytes_with_metadata, &self.cover_bytes, format)?;
+        self.audio_bytes = audio_bytes_with_cover.into();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command::{
+        extract_metadata,
+        tests::{ffmpeg_available, sine_audio},
+    };
+
+    fn sine_frames(count: usize) -> Vec<Frame> {
+        (0..count)
+            .map(|index| {
+                let sample = (index as f32 * 0.01).sin();
+                Frame::new(sample, sample * 0.5)
+            })
+            .collect()
+    }
+
+    fn assert_waveform(waveform: &Waveform) {
+        assert_eq!(waveform.0.len(), WAVEFORM_RESOLUTION);
+        assert!(waveform.0.iter().all(|value| (0.0..=1.).contains(value)));
+    }
+
+    #[test]
+    fn waveforms_from_short_buffer() {
+        let (peaks, rms) = waveforms_from_frames(&sine_frames(10));
+        assert_waveform(&peaks);
+        assert_waveform(&rms);
+    }
+
+    #[test]
+    fn waveforms_from_long_buffer() {
+        let (peaks, rms) = waveforms_from_frames(&sine_frames(1_000_000));
+        assert_waveform(&peaks);
+        assert_waveform(&rms);
+        assert!(peaks.0.iter().any(|&value| value == 1.));
+    }
+
+    #[test]
+    fn unseparated_tags_fall_back_when_written() {
+        if !ffmpeg_available() {
+            return;
+        }
+        let mut song = Song {
+            title: String::from("some title"),
+            artist: String::from("some artist"),
+            composer: String::from("ignored composer"),
+            album_artist: String::from("separate album artist"),
+            separate_album_artist: true,
+            audio_bytes: sine_audio("mp3").into(),
+            ..Default::default()
+        };
+        song.update_bytes_from_metadata(false, false).unwrap();
+
+        let metadata = extract_metadata(&song.audio_bytes).unwrap();
+        assert_eq!(json_read(&metadata, "album"), "some title");
+        assert_eq!(json_read(&metadata, "album_artist"), "separate album artist");
+        assert_eq!(json_read(&metadata, "composer"), "some artist");
+    }
+
+    #[test]
+    fn provenance_is_written_next_to_the_year() {
+        if !ffmpeg_available() {
+            return;
+        }
+        let mut song = Song {
+            title: String::from("some title"),
+            year: String::from("1999"),
+            audio_bytes: sine_audio("mp3").into(),
+            ..Default::default()
+        };
+        song.update_bytes_from_metadata(false, true).unwrap();
+
+        let metadata = extract_metadata(&song.audio_bytes).unwrap();
+        assert_eq!(json_read(&metadata, "encoded_by"), encoded_by());
+        assert_eq!(json_read(&metadata, "date"), "1999");
+        let comment = json_read(&metadata, "comment");
+        assert!(comment.starts_with(&format!("encoded by {}", encoded_by())));
+    }
+
+    #[test]
+    fn waveforms_from_empty_buffer() {
+        let (peaks, rms) = waveforms_from_frames(&[]);
+        assert_waveform(&peaks);
+        assert_waveform(&rms);
+    }
+
+    #[test]
+    fn track_numbers_allow_a_total() {
+        for track in ["1", "12", "3/12"] {
+            assert!(is_track_number(track), "{track}");
+        }
+        for track in ["0", "A1", "3/", "/12", "3/0", "-1"] {
+            assert!(!is_track_number(track), "{track}");
+        }
+    }
+
+    #[test]
+    fn title_junk_matches_whole_words() {
+        let junk_patterns = DEFAULT_TITLE_JUNK_PATTERNS.map(String::from);
+        let cleaned = |title: &str| {
+            let mut song = Song { title: title.to_string(), ..Default::default() };
+            song.clean_title(&junk_patterns);
+            (song.artist, song.title)
+        };
+        assert_eq!(cleaned("Song (Official Video) [HD]").1, "Song");
+        assert_eq!(cleaned("Song (Lyrics) (feat. Someone)").1, "Song");
+        assert_eq!(cleaned("Song (Defeated)").1, "Song (Defeated)");
+        assert_eq!(cleaned("Song (Audiomachine Remix)").1, "Song (Audiomachine Remix)");
+        assert_eq!(
+            cleaned("Artist - Song ft. Someone"),
+            (String::from("Artist"), String::from("Song"))
+        );
+    }
+}